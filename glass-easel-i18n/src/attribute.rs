@@ -0,0 +1,104 @@
+use crate::{
+    placeholder::{CONTEXT_ATTR, IGNORE_ATTR},
+    Diagnostic, DiagnosticKind,
+};
+use std::str::FromStr;
+
+/// A rule selecting the attributes to translate.
+///
+/// The rules are written as:
+///
+/// - `title`, `*:title` or `[title]`, the `title` attribute of any element;
+/// - `my-dialog:title` or `my-dialog[title]`, the `title` attribute of `<my-dialog>`;
+/// - `my-dialog:*`, all attributes of `<my-dialog>` except `i18n-context` and `i18n-ignore`.
+///
+/// A rule prefixed with `!` like `!my-dialog:title` excludes the attributes instead,
+/// which takes precedence over the including rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeRule {
+    pub exclude: bool,
+    /// The tag name of the elements, or `None` for any element.
+    pub tag_name: Option<String>,
+    /// The attribute name, or `None` for any attribute.
+    pub attribute: Option<String>,
+}
+
+/// The name in a rule, or `Some(None)` for `*`.
+fn rule_name(name: &str) -> Option<Option<String>> {
+    let name = name.trim();
+    if name == "*" {
+        return Some(None);
+    }
+    let is_valid = !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '[' | ']' | ':' | '!' | '*'));
+    is_valid.then(|| Some(name.to_string()))
+}
+
+impl FromStr for AttributeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let rule = s.trim();
+            let (exclude, rule) = match rule.strip_prefix('!') {
+                Some(rule) => (true, rule),
+                None => (false, rule),
+            };
+            let (tag_name, attribute) = match rule.strip_suffix(']') {
+                Some(rule) => rule.split_once('[')?,
+                None => rule.split_once(':').unwrap_or(("*", rule)),
+            };
+            // `[title]` selects the attribute of any element like CSS
+            let tag_name = match tag_name.trim() {
+                "" => None,
+                x => rule_name(x)?,
+            };
+            Some(Self {
+                exclude,
+                tag_name,
+                attribute: rule_name(attribute)?,
+            })
+        };
+        parse().ok_or_else(|| format!("Invalid attribute rule: {:?}", s))
+    }
+}
+
+impl AttributeRule {
+    fn matches(&self, tag_name: &str, attribute: &str) -> bool {
+        self.tag_name.as_deref().is_none_or(|x| x == tag_name)
+            && self.attribute.as_deref().is_none_or(|x| x == attribute)
+    }
+}
+
+/// Parse the attribute rules in the options, see [`AttributeRule`].
+pub(crate) fn parse_attribute_rules(
+    path: &str,
+    rules: &[String],
+) -> Result<Vec<AttributeRule>, Diagnostic> {
+    rules
+        .iter()
+        .map(|x| x.parse())
+        .collect::<Result<_, String>>()
+        .map_err(|message| {
+            Diagnostic::without_location(DiagnosticKind::InvalidOption, path, message)
+        })
+}
+
+/// Whether the attribute of an element is selected by an including rule and not excluded by any rule.
+///
+/// The `i18n-context` and `i18n-ignore` attributes are never selected.
+pub fn is_attribute_included(rules: &[AttributeRule], tag_name: &str, attribute: &str) -> bool {
+    if attribute == CONTEXT_ATTR || attribute == IGNORE_ATTR {
+        return false;
+    }
+    let mut included = false;
+    for rule in rules.iter().filter(|x| x.matches(tag_name, attribute)) {
+        if rule.exclude {
+            return false;
+        }
+        included = true;
+    }
+    included
+}
@@ -0,0 +1,111 @@
+use super::{
+    translate::{remove_i18n_meta_tags, translate},
+    LocaleContext,
+};
+use crate::parse_additional_template;
+use glass_easel_template_compiler::parse::{
+    expr::Expression,
+    tag::{Element, ElementKind, Node, Value},
+    Position,
+};
+use std::ops::Range;
+
+// the Position of else_branch or branches just placed by the position of the template's first child
+pub(super) fn get_first_child_position(template: &[Node]) -> Option<Range<Position>> {
+    let position: Option<Range<Position>>;
+    match template.first()? {
+        Node::Element(element) => {
+            position = Some(element.tag_location.close.clone());
+        }
+        Node::Text(value) => match value {
+            Value::Dynamic {
+                double_brace_location,
+                ..
+            } => {
+                let (first_location, _) = double_brace_location;
+                position = Some(first_location.clone());
+            }
+            Value::Static { location, .. } => {
+                position = Some(location.clone());
+            }
+            _ => {
+                return None;
+            }
+        },
+        Node::Comment(x) => {
+            position = Some(x.location.clone());
+        }
+        Node::UnknownMetaTag(x) => {
+            position = Some(x.location.clone());
+        }
+        _ => {
+            return None;
+        }
+    }
+    position
+}
+
+/// The condition which selects the locale.
+pub(super) fn locale_condition(
+    locale: &Expression,
+    lang: &str,
+    position: &Range<Position>,
+) -> Box<Expression> {
+    Box::new(Expression::EqFull {
+        left: Box::new(locale.clone()),
+        right: Box::new(Expression::LitStr {
+            value: lang.into(),
+            location: position.clone(),
+        }),
+        location: position.clone(),
+    })
+}
+
+/// Generate a `<block wx:if>` chain with the branches.
+pub(super) fn new_if_block(
+    branches: Vec<(Range<Position>, Value, Vec<Node>)>,
+    else_branch: Option<(Range<Position>, Vec<Node>)>,
+    position: &Range<Position>,
+) -> Element {
+    let mut if_block_template =
+        parse_additional_template(r#"<block wx:if="" /><block wx:else="" />"#);
+    let Node::Element(mut if_block) = if_block_template.content.pop().unwrap() else {
+        panic!()
+    };
+    if_block.tag_location.start = (position.clone(), position.clone());
+    if_block.tag_location.close = position.clone();
+    if_block.tag_location.end = Some((position.clone(), position.clone()));
+    let ElementKind::If {
+        branches: new_branches,
+        else_branch: new_else_branch,
+        ..
+    } = &mut if_block.kind
+    else {
+        panic!()
+    };
+    *new_branches = branches;
+    *new_else_branch = else_branch;
+    if_block
+}
+
+/// Wrap a translated copy of the nodes for each locale in a `<block wx:if>` chain.
+pub(super) fn translate_template(template: Vec<Node>, ctx: LocaleContext) -> Element {
+    // generate branch content
+    let mut branches: Vec<(Range<Position>, Value, Vec<Node>)> = vec![];
+    let branch_position = get_first_child_position(&template).unwrap_or_default();
+    for (lang, trans_content_map) in ctx.trans_content.map.iter() {
+        let mut template_item = template.clone();
+        let branch_value = Value::new_expression(
+            locale_condition(ctx.locale, lang, &branch_position),
+            (branch_position.clone(), branch_position.clone()),
+        );
+        translate(&mut template_item, trans_content_map, ctx.options);
+        branches.push((branch_position.clone(), branch_value, template_item));
+    }
+    let mut else_branch_template = template;
+    remove_i18n_meta_tags(&mut else_branch_template);
+    let else_branch = Some((branch_position.clone(), else_branch_template));
+
+    // generate a new node
+    new_if_block(branches, else_branch, &branch_position)
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+/// Fallback chains of locales resolved at compile time.
+///
+/// A message missing in a locale is taken from the first locale in its chain which translates it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocaleFallbacks {
+    map: Vec<(String, Vec<String>)>,
+}
+
+impl LocaleFallbacks {
+    /// Let `locale` fall back to `fallback`, after the fallbacks added before.
+    pub fn add(&mut self, locale: &str, fallback: &str) {
+        let index = match self.map.iter().position(|x| x.0 == locale) {
+            Some(index) => index,
+            None => {
+                self.map.push((locale.to_string(), vec![]));
+                self.map.len() - 1
+            }
+        };
+        let list = &mut self.map[index].1;
+        if locale != fallback && !list.iter().any(|x| x == fallback) {
+            list.push(fallback.to_string());
+        }
+    }
+
+    /// Add a chain like `zh-hk -> zh-tw -> zh`, in which each locale falls back to the next one.
+    pub fn add_chain(&mut self, chain: &str) {
+        let locales: Vec<&str> = chain
+            .split("->")
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .collect();
+        for pair in locales.windows(2) {
+            self.add(pair[0], pair[1]);
+        }
+    }
+
+    /// The locales which have fallbacks.
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.map.iter().map(|x| x.0.as_str())
+    }
+
+    /// The fallbacks of the locale in order, following the chains of the fallbacks themselves.
+    pub fn chain(&self, locale: &str) -> Vec<&str> {
+        fn rec<'a>(this: &'a LocaleFallbacks, locale: &str, root: &str, ret: &mut Vec<&'a str>) {
+            let Some((_, list)) = this.map.iter().find(|x| x.0 == locale) else {
+                return;
+            };
+            for fallback in list {
+                if fallback == root || ret.contains(&fallback.as_str()) {
+                    continue;
+                }
+                ret.push(fallback);
+                rec(this, fallback, root, ret);
+            }
+        }
+        let mut ret = vec![];
+        rec(self, locale, locale, &mut ret);
+        ret
+    }
+}
+
+/// A message of the template translated with a fallback locale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackUsage {
+    pub locale: String,
+    pub msgid: String,
+    /// The locale which the translation is taken from.
+    pub fallback: String,
+}
+
+impl std::fmt::Display for FallbackUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} in {} falls back to {}",
+            self.msgid, self.locale, self.fallback
+        )
+    }
+}
+
+/// The locale which each translation is taken from, keyed by locale and then msgid.
+pub(super) type FallbackSources = HashMap<String, HashMap<String, String>>;
+
+/// Fill the missing translations of each locale from its fallbacks.
+///
+/// Locales which only have fallbacks are added if any of the fallbacks exist.
+pub(super) fn apply_fallbacks<V: Clone>(
+    map: &mut HashMap<String, HashMap<String, V>>,
+    fallbacks: &LocaleFallbacks,
+) -> FallbackSources {
+    for locale in fallbacks.locales() {
+        if !map.contains_key(locale) && fallbacks.chain(locale).iter().any(|x| map.contains_key(*x))
+        {
+            map.insert(locale.to_string(), HashMap::new());
+        }
+    }
+    let original = map.clone();
+    let mut sources = FallbackSources::new();
+    for (locale, locale_map) in map.iter_mut() {
+        for fallback in fallbacks.chain(locale) {
+            let Some(fallback_map) = original.get(fallback) else {
+                continue;
+            };
+            for (msg_id, msg_str) in fallback_map {
+                if locale_map.contains_key(msg_id) {
+                    continue;
+                }
+                locale_map.insert(msg_id.clone(), msg_str.clone());
+                sources
+                    .entry(locale.clone())
+                    .or_default()
+                    .insert(msg_id.clone(), fallback.to_string());
+            }
+        }
+    }
+    sources
+}
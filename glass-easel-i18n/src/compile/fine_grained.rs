@@ -0,0 +1,230 @@
+use super::{
+    branches::{get_first_child_position, locale_condition, new_if_block},
+    translate::{
+        inline_to_string, set_value_expression, translate_entire_children, translate_literal,
+        translate_unit, translate_value,
+    },
+    LocaleContext, LocaleMessages,
+};
+use crate::{
+    has_i18n_translate_children, visit_group, visit_message_literals, visit_units,
+    visit_value_literals, ExtractOptions, TranslatableUnit, UnitMessage, UnitTarget,
+};
+use glass_easel_template_compiler::parse::{
+    expr::Expression,
+    tag::{Node, Value},
+    TemplateStructure,
+};
+
+/// The string expression of a value.
+fn value_expression(value: &Value) -> Option<Box<Expression>> {
+    match value {
+        Value::Static {
+            value, location, ..
+        } => Some(Box::new(Expression::LitStr {
+            value: value.as_str().into(),
+            location: location.clone(),
+        })),
+        Value::Dynamic { expression, .. } => {
+            let mut expression = expression.clone();
+            inline_to_string(&mut expression);
+            Some(expression)
+        }
+        _ => None,
+    }
+}
+
+/// The message with the string literals inside its placeholders translated with the translations of a single locale.
+fn translate_message_literals(
+    message: &UnitMessage,
+    context: Option<&str>,
+    ctx: LocaleContext,
+    trans_content_map: &LocaleMessages,
+) -> UnitMessage {
+    let mut message = message.clone();
+    visit_message_literals(&mut message, context, ctx.options, &mut |unit| {
+        translate_unit(unit, trans_content_map)
+    });
+    message
+}
+
+/// Replace the string literals inside the data bindings of a value with conditional expressions
+/// selecting the translations by the locale, except for the `translated` locales.
+fn translate_value_literals_by_locale(
+    value: &mut Value,
+    context: Option<&str>,
+    translated: &[&str],
+    ctx: LocaleContext,
+) {
+    visit_value_literals(value, context, ctx.options, &mut |unit| {
+        if let UnitTarget::Literal(expression) = unit.target {
+            translate_literal_by_locale(expression, &unit.message, translated, ctx);
+        }
+        true
+    });
+}
+
+/// Replace the value with a conditional expression selecting the translation by the locale.
+///
+/// The string literals inside are translated along with the value in the locales which translate the value,
+/// and selected by the locale one by one for the others.
+/// The value is unchanged if there is no translation in any locale.
+pub(super) fn translate_value_by_locale(
+    value: &mut Value,
+    message: &UnitMessage,
+    ctx: LocaleContext,
+) {
+    let context = message.context.as_deref();
+    let mut translated = vec![];
+    for (lang, trans_content_map) in ctx.locale_messages() {
+        let message = translate_message_literals(message, context, ctx, trans_content_map);
+        let mut translated_value = value.clone();
+        if translate_value(&mut translated_value, &message, trans_content_map) {
+            translated.push((lang, translated_value));
+        }
+    }
+    let langs: Vec<&str> = translated.iter().map(|x| x.0.as_str()).collect();
+    translate_value_literals_by_locale(value, context, &langs, ctx);
+    if translated.is_empty() {
+        return;
+    }
+    let Some(fallback) = value_expression(value) else {
+        return;
+    };
+    let location = value.location();
+    let expression =
+        translated
+            .into_iter()
+            .rev()
+            .fold(fallback, |acc, (lang, translated_value)| {
+                Box::new(Expression::Cond {
+                    cond: locale_condition(ctx.locale, lang, &location),
+                    true_br: value_expression(&translated_value).unwrap(),
+                    false_br: acc,
+                    question_location: location.clone(),
+                    colon_location: location.clone(),
+                })
+            });
+    set_value_expression(value, expression);
+}
+
+/// Replace a string literal inside a data binding with a conditional expression selecting the translation by the locale.
+///
+/// The `skipped` locales are left out, which translate the enclosing message along with the literal.
+fn translate_literal_by_locale(
+    expression: &mut Expression,
+    message: &UnitMessage,
+    skipped: &[&str],
+    ctx: LocaleContext,
+) {
+    let location = expression.location();
+    let mut ret = expression.clone();
+    for (lang, trans_content_map) in ctx.locale_messages().rev() {
+        if skipped.contains(&lang.as_str()) {
+            continue;
+        }
+        let mut translated = expression.clone();
+        if translate_literal(&mut translated, message, trans_content_map) {
+            ret = Expression::Cond {
+                cond: locale_condition(ctx.locale, lang, &location),
+                true_br: Box::new(translated),
+                false_br: Box::new(ret),
+                question_location: location.clone(),
+                colon_location: location.clone(),
+            };
+        }
+    }
+    *expression = ret;
+}
+
+/// Wrap the children of an element with `<!I18N translate-children>` in a `<block wx:if>` chain
+/// containing only the locales which translate them.
+///
+/// `context` is the message context inherited by the units inside the group.
+pub(super) fn translate_children_by_locale(
+    node_list: &mut Vec<Node>,
+    message: &UnitMessage,
+    context: Option<&str>,
+    ctx: LocaleContext,
+) {
+    let ctx = with_message_literals(ctx);
+    let position = get_first_child_position(node_list).unwrap_or_default();
+    let mut branches = vec![];
+    let mut langs = vec![];
+    for (lang, trans_content_map) in ctx.locale_messages() {
+        let message = translate_message_literals(message, context, ctx, trans_content_map);
+        let mut translated = node_list.clone();
+        if translate_entire_children(&mut translated, &message, trans_content_map) {
+            langs.push(lang.as_str());
+            visit_group(&mut translated, ctx.options, context, &mut |unit| {
+                translate_unit(unit, trans_content_map)
+            });
+            let branch_value = Value::new_expression(
+                locale_condition(ctx.locale, lang, &position),
+                (position.clone(), position.clone()),
+            );
+            branches.push((position.clone(), branch_value, translated));
+        }
+    }
+    node_list.retain(
+        |node| !matches!(node, Node::UnknownMetaTag(tag, ..) if has_i18n_translate_children(tag)),
+    );
+    // the else branch is only reached by the locales without a branch
+    let skipped_locales: Vec<&str> = ctx.skipped_locales.iter().copied().chain(langs).collect();
+    let ctx = LocaleContext {
+        skipped_locales: &skipped_locales,
+        ..ctx
+    };
+    for node in node_list.iter_mut() {
+        if let Node::Text(value) = node {
+            translate_value_literals_by_locale(value, context, &[], ctx);
+        }
+    }
+    visit_group(node_list, ctx.options, context, &mut |unit| {
+        translate_unit_by_locale(unit, ctx)
+    });
+    if !branches.is_empty() {
+        let else_branch = Some((position.clone(), std::mem::take(node_list)));
+        *node_list = vec![Node::Element(new_if_block(
+            branches,
+            else_branch,
+            &position,
+        ))];
+    }
+}
+
+/// Translate a unit selecting the translation by the locale, returning whether to visit the units inside it.
+fn translate_unit_by_locale(unit: TranslatableUnit, ctx: LocaleContext) -> bool {
+    match unit.target {
+        UnitTarget::Value(value) => {
+            translate_value_by_locale(value, &unit.message, ctx);
+            true
+        }
+        UnitTarget::Children(children) => {
+            let context = unit.inherited_context.as_deref();
+            translate_children_by_locale(children, &unit.message, context, ctx);
+            false
+        }
+        UnitTarget::Literal(expression) => {
+            translate_literal_by_locale(expression, &unit.message, &[], ctx);
+            true
+        }
+    }
+}
+
+/// The context translating the string literals along with the values and the groups containing them.
+fn with_message_literals(ctx: LocaleContext) -> LocaleContext {
+    let options = ExtractOptions {
+        literals_with_messages: true,
+        ..ctx.options
+    };
+    LocaleContext { options, ..ctx }
+}
+
+/// Translate the nodes in place, selecting the translation by the locale only where translations exist.
+pub(super) fn translate_fine_grained(node_list: &mut [Node], ctx: LocaleContext) {
+    let ctx = with_message_literals(ctx);
+    visit_units(node_list, ctx.options, None, &mut |unit| {
+        translate_unit_by_locale(unit, ctx)
+    });
+}
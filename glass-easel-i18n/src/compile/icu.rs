@@ -0,0 +1,469 @@
+use super::{
+    parse_expression,
+    plural::{plural_categories, substitute_count},
+    translate::inline_to_string,
+};
+use glass_easel_template_compiler::parse::{expr::Expression, Position};
+use std::{collections::HashMap, ops::Range, str::FromStr};
+
+/// The syntax of the translations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// The text with `{{name}}` placeholders, the same as the msgids.
+    #[default]
+    Plain,
+    /// ICU MessageFormat, with `{name}` arguments and `plural` or `select` arguments
+    /// like `{count, plural, one {# file} other {# files}}`.
+    ///
+    /// The `{{name}}` placeholders are also accepted as arguments.
+    Icu,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "icu" => Ok(Self::Icu),
+            _ => Err(format!("Unknown message format: {}", s)),
+        }
+    }
+}
+
+/// A part of an ICU message.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum IcuPart {
+    Text(String),
+    /// `{name}`, or `{{name}}` as in the msgids.
+    Argument(String),
+    /// `#` in a plural case, which is replaced with the plural argument of the name.
+    Pound(String),
+    Plural {
+        name: String,
+        cases: Vec<(String, Vec<IcuPart>)>,
+    },
+    Select {
+        name: String,
+        cases: Vec<(String, Vec<IcuPart>)>,
+    },
+}
+
+/// A translation in ICU MessageFormat which selects its text by the bound values.
+#[derive(Debug, Clone)]
+pub struct IcuMessage {
+    pub(super) parts: Vec<IcuPart>,
+    /// The plural categories with their rules, which follow the language of the translation.
+    pub(super) categories: Vec<(&'static str, Expression)>,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(format!("expected `{}` but found `{}`", expected, c)),
+            None => Err(format!("expected `{}` but found the end", expected)),
+        }
+    }
+
+    /// An argument name, argument type or case selector.
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !matches!(c, ',' | '{' | '}'))
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Parse the parts until an unmatched `}` or the end.
+    ///
+    /// `plural` is the name of the closest plural argument, which `#` refers to.
+    fn message(&mut self, plural: Option<&str>) -> Result<Vec<IcuPart>, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '}' => break,
+                '{' => {
+                    if !text.is_empty() {
+                        parts.push(IcuPart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(self.argument(plural)?);
+                }
+                '#' if plural.is_some() => {
+                    if !text.is_empty() {
+                        parts.push(IcuPart::Text(std::mem::take(&mut text)));
+                    }
+                    self.pos += 1;
+                    parts.push(IcuPart::Pound(plural.unwrap_or_default().to_string()));
+                }
+                '\'' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('\'') => {
+                            self.pos += 1;
+                            text.push('\'');
+                        }
+                        // the special characters are quoted until the next single apostrophe
+                        Some('{' | '}' | '#') => {
+                            while let Some(c) = self.peek() {
+                                self.pos += 1;
+                                if c != '\'' {
+                                    text.push(c);
+                                } else if self.peek() == Some('\'') {
+                                    self.pos += 1;
+                                    text.push('\'');
+                                } else {
+                                    break;
+                                }
+                            }
+                        }
+                        _ => text.push('\''),
+                    }
+                }
+                c => {
+                    self.pos += 1;
+                    text.push(c);
+                }
+            }
+        }
+        if !text.is_empty() {
+            parts.push(IcuPart::Text(text));
+        }
+        Ok(parts)
+    }
+
+    fn argument(&mut self, plural: Option<&str>) -> Result<IcuPart, String> {
+        self.expect('{')?;
+        if self.peek() == Some('{') {
+            let start = self.pos + 1;
+            let len = self.chars[start..]
+                .windows(2)
+                .position(|x| x == ['}', '}'])
+                .ok_or("unclosed placeholder")?;
+            self.pos = start + len + 2;
+            let name: String = self.chars[start..start + len].iter().collect();
+            return Ok(IcuPart::Argument(name.trim().to_string()));
+        }
+        self.skip_whitespace();
+        let name = self.word();
+        if name.is_empty() {
+            return Err("missing argument name".to_string());
+        }
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(IcuPart::Argument(name));
+        }
+        self.expect(',')?;
+        self.skip_whitespace();
+        let kind = self.word();
+        self.skip_whitespace();
+        self.expect(',')?;
+        let cases = match kind.as_str() {
+            "plural" => self.cases(Some(&name), true)?,
+            "select" => self.cases(plural, false)?,
+            _ => return Err(format!("unsupported argument type `{}`", kind)),
+        };
+        if !cases.iter().any(|x| x.0 == "other") {
+            return Err(format!("missing `other` case of argument `{}`", name));
+        }
+        self.expect('}')?;
+        Ok(match kind.as_str() {
+            "plural" => IcuPart::Plural { name, cases },
+            _ => IcuPart::Select { name, cases },
+        })
+    }
+
+    fn cases(
+        &mut self,
+        plural: Option<&str>,
+        is_plural: bool,
+    ) -> Result<Vec<(String, Vec<IcuPart>)>, String> {
+        let mut cases = vec![];
+        loop {
+            self.skip_whitespace();
+            if matches!(self.peek(), Some('}') | None) {
+                break;
+            }
+            let selector = self.word();
+            if selector.is_empty() {
+                return Err("missing case selector".to_string());
+            }
+            if let Some(value) = selector.strip_prefix('=') {
+                if !is_plural || value.parse::<f64>().is_err() {
+                    return Err(format!("invalid case selector `{}`", selector));
+                }
+            }
+            if selector.starts_with("offset:") {
+                return Err("plural offsets are not supported".to_string());
+            }
+            self.skip_whitespace();
+            self.expect('{')?;
+            let message = self.message(plural)?;
+            self.expect('}')?;
+            cases.push((selector, message));
+        }
+        Ok(cases)
+    }
+}
+
+/// Parse a translation in ICU MessageFormat.
+pub(super) fn parse_icu_message(src: &str) -> Result<Vec<IcuPart>, String> {
+    let mut parser = Parser {
+        chars: src.chars().collect(),
+        pos: 0,
+    };
+    let parts = parser.message(None)?;
+    if parser.peek().is_some() {
+        return Err("unmatched `}`".to_string());
+    }
+    Ok(parts)
+}
+
+/// The message with `{{name}}` placeholders if it contains no `plural` or `select` arguments.
+pub(super) fn placeholder_message(parts: &[IcuPart]) -> Option<String> {
+    let mut ret = String::new();
+    for part in parts {
+        match part {
+            IcuPart::Text(text) => ret.push_str(text),
+            IcuPart::Argument(name) => ret.push_str(&format!("{{{{{}}}}}", name)),
+            _ => return None,
+        }
+    }
+    Some(ret)
+}
+
+/// The names of all arguments used in the message, in order.
+pub(super) fn message_arguments(parts: &[IcuPart]) -> Vec<String> {
+    fn rec(parts: &[IcuPart], ret: &mut Vec<String>) {
+        for part in parts {
+            let (name, cases) = match part {
+                IcuPart::Text(_) | IcuPart::Pound(_) => continue,
+                IcuPart::Argument(name) => (name, None),
+                IcuPart::Plural { name, cases } | IcuPart::Select { name, cases } => {
+                    (name, Some(cases))
+                }
+            };
+            if !ret.contains(name) {
+                ret.push(name.clone());
+            }
+            for (_, message) in cases.into_iter().flatten() {
+                rec(message, ret);
+            }
+        }
+    }
+    let mut ret = vec![];
+    rec(parts, &mut ret);
+    ret
+}
+
+/// The plural categories of the locale with their parsed rules.
+fn parse_plural_categories(locale: &str) -> Vec<(&'static str, Expression)> {
+    plural_categories(locale)
+        .iter()
+        .map(|(category, rule)| {
+            let rule = parse_expression(rule).expect("the built-in plural rules are valid");
+            (*category, rule)
+        })
+        .collect()
+}
+
+/// Split the ICU messages of a locale from the translations.
+///
+/// The messages without `plural` or `select` arguments are converted to `{{name}}` placeholders in place,
+/// so they are translated like the plain ones.
+/// The translations which cannot be parsed are removed and returned with the errors.
+pub(super) fn split_icu_messages(
+    locale: &str,
+    messages: &mut HashMap<String, String>,
+    sources: Option<&HashMap<String, String>>,
+) -> (HashMap<String, IcuMessage>, HashMap<String, String>) {
+    let mut icu_messages = HashMap::new();
+    let mut errors = HashMap::new();
+    // the rules are parsed once for each language
+    let mut locale_categories = HashMap::new();
+    messages.retain(|key, translation| match parse_icu_message(translation) {
+        Ok(parts) => {
+            match placeholder_message(&parts) {
+                Some(message) => *translation = message,
+                None => {
+                    // the plural categories follow the language of the translation
+                    let locale = sources.and_then(|x| x.get(key)).map_or(locale, |x| x);
+                    let categories = locale_categories
+                        .entry(locale.to_string())
+                        .or_insert_with(|| parse_plural_categories(locale))
+                        .clone();
+                    let message = IcuMessage { parts, categories };
+                    icu_messages.insert(key.clone(), message);
+                }
+            }
+            true
+        }
+        Err(err) => {
+            errors.insert(key.clone(), err);
+            false
+        }
+    });
+    (icu_messages, errors)
+}
+
+/// Builds the expression of an ICU message.
+struct ExpressionBuilder<'a> {
+    args: &'a [(String, Box<Expression>)],
+    /// The plural categories of the locale with their rules.
+    categories: &'a [(&'static str, Expression)],
+    location: &'a Range<Position>,
+}
+
+impl ExpressionBuilder<'_> {
+    fn arg(&self, name: &str) -> Option<Box<Expression>> {
+        self.args.iter().find(|x| x.0 == name).map(|x| x.1.clone())
+    }
+
+    fn lit_str(&self, value: &str) -> Box<Expression> {
+        Box::new(Expression::LitStr {
+            value: value.into(),
+            location: self.location.clone(),
+        })
+    }
+
+    /// `cond_0 ? value_0 : cond_1 ? value_1 : ... : other`
+    fn select(
+        &self,
+        cases: Vec<(Box<Expression>, Box<Expression>)>,
+        other: Box<Expression>,
+    ) -> Box<Expression> {
+        cases.into_iter().rev().fold(other, |acc, (cond, value)| {
+            Box::new(Expression::Cond {
+                cond,
+                true_br: value,
+                false_br: acc,
+                question_location: self.location.clone(),
+                colon_location: self.location.clone(),
+            })
+        })
+    }
+
+    fn plural(&self, name: &str, cases: &[(String, Vec<IcuPart>)]) -> Option<Box<Expression>> {
+        let value = self.arg(name)?;
+        let mut conds = vec![];
+        for (selector, message) in cases {
+            let Some(number) = selector.strip_prefix('=') else {
+                continue;
+            };
+            let number = match number.parse::<i64>() {
+                Ok(value) => Expression::LitInt {
+                    value,
+                    location: self.location.clone(),
+                },
+                Err(_) => Expression::LitFloat {
+                    value: number.parse().ok()?,
+                    location: self.location.clone(),
+                },
+            };
+            let cond = Box::new(Expression::Eq {
+                left: value.clone(),
+                right: Box::new(number),
+                location: self.location.clone(),
+            });
+            conds.push((cond, self.message(message)?));
+        }
+        for (category, rule) in self.categories {
+            if let Some((_, message)) = cases.iter().find(|x| x.0 == *category) {
+                let cond = Box::new(substitute_count(rule, &value));
+                conds.push((cond, self.message(message)?));
+            }
+        }
+        let (_, other) = cases.iter().find(|x| x.0 == "other")?;
+        Some(self.select(conds, self.message(other)?))
+    }
+
+    fn select_case(&self, name: &str, cases: &[(String, Vec<IcuPart>)]) -> Option<Box<Expression>> {
+        let value = self.arg(name)?;
+        let mut conds = vec![];
+        for (selector, message) in cases {
+            if selector == "other" {
+                continue;
+            }
+            let cond = Box::new(Expression::EqFull {
+                left: value.clone(),
+                right: self.lit_str(selector),
+                location: self.location.clone(),
+            });
+            conds.push((cond, self.message(message)?));
+        }
+        let (_, other) = cases.iter().find(|x| x.0 == "other")?;
+        Some(self.select(conds, self.message(other)?))
+    }
+
+    fn message(&self, parts: &[IcuPart]) -> Option<Box<Expression>> {
+        let mut exprs = vec![];
+        for part in parts {
+            exprs.push(match part {
+                IcuPart::Text(text) => self.lit_str(text),
+                IcuPart::Argument(name) | IcuPart::Pound(name) => {
+                    Box::new(Expression::ToStringWithoutUndefined {
+                        value: self.arg(name)?,
+                        location: self.location.clone(),
+                    })
+                }
+                IcuPart::Plural { name, cases } => self.plural(name, cases)?,
+                IcuPart::Select { name, cases } => self.select_case(name, cases)?,
+            });
+        }
+        // start with a string so that the arguments are concatenated instead of added
+        let is_str = |x: &Expression| matches!(x, Expression::LitStr { .. });
+        if exprs.len() > 1 && !is_str(&exprs[0]) && !is_str(&exprs[1]) {
+            exprs.insert(0, self.lit_str(""));
+        }
+        let mut exprs = exprs.into_iter();
+        let first = exprs.next().unwrap_or_else(|| self.lit_str(""));
+        Some(exprs.fold(first, |acc, x| {
+            Box::new(Expression::Plus {
+                left: acc,
+                right: x,
+                location: self.location.clone(),
+            })
+        }))
+    }
+}
+
+/// The string expression of an ICU message, filling the arguments with the expressions in `args`.
+///
+/// Returns `None` if any argument is not in `args`.
+pub(super) fn icu_expression(
+    message: &IcuMessage,
+    args: &[(String, Box<Expression>)],
+    location: &Range<Position>,
+) -> Option<Box<Expression>> {
+    let builder = ExpressionBuilder {
+        args,
+        categories: &message.categories,
+        location,
+    };
+    let mut ret = builder.message(&message.parts)?;
+    inline_to_string(&mut ret);
+    Some(ret)
+}
@@ -0,0 +1,321 @@
+use super::{
+    fine_grained::{translate_children_by_locale, translate_value_by_locale},
+    translate::{set_value_expression, split_translated_str, with_outer_whitespace},
+    LocaleContext, OrderedTransContent,
+};
+use crate::{
+    placeholder::placeholder_name, visit_units, ExtractOptions, LiteralPolicy, TermOrigin,
+    UnitMessage, UnitTarget,
+};
+use glass_easel_template_compiler::parse::{
+    expr::Expression,
+    tag::{Node, Value},
+    Position, TemplateStructure,
+};
+use regex::Regex;
+use std::{fmt::Write, ops::Range};
+
+/// A part of a message in a [`MessageTable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageSegment {
+    Text(String),
+    /// The placeholder with the index in the arguments of `t`.
+    Placeholder(usize),
+}
+
+/// The messages of a locale keyed by msgid.
+pub type Messages = Vec<(String, Vec<MessageSegment>)>;
+
+/// The translated messages used by a template compiled with the lookup-table strategy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageTable {
+    /// The messages keyed by msgid for each locale, sorted by locale.
+    ///
+    /// Messages without translations are not included, and `t` falls back to the msgid.
+    pub locales: Vec<(String, Messages)>,
+}
+
+/// Write a string literal which is valid in both JSON and JavaScript.
+fn write_js_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{2028}' | '\u{2029}' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The runtime of the generated WXS module.
+///
+/// `t(locale, key, ...args)` fills the placeholders of the message with `args`,
+/// or the placeholders of `key` if the message is not found,
+/// in which `args` are the distinct placeholders in order.
+/// `tc(locale, context, key, ...args)` does the same for a message with a context.
+const WXS_RUNTIME: &str = r#"function str(x) {
+  return x === undefined ? "" : x;
+}
+function format(locale, id, key, args, offset) {
+  var table = messages[locale];
+  var message = table ? table[id] : undefined;
+  var ret = "";
+  if (message === undefined) {
+    var names = [];
+    var last = 0;
+    while (true) {
+      var start = key.indexOf("{{", last);
+      var end = start < 0 ? -1 : key.indexOf("}}", start + 2);
+      if (end < 0) break;
+      var name = key.slice(start + 2, end);
+      var index = names.indexOf(name);
+      if (index < 0) {
+        index = names.length;
+        names.push(name);
+      }
+      ret += key.slice(last, start) + str(args[index + offset]);
+      last = end + 2;
+    }
+    return ret + key.slice(last);
+  }
+  for (var i = 0; i < message.length; i += 1) {
+    var segment = message[i];
+    ret += typeof segment === "number" ? str(args[segment + offset]) : segment;
+  }
+  return ret;
+}
+function t(locale, key) {
+  return format(locale, key, key, arguments, 2);
+}
+function tc(locale, context, key) {
+  return format(locale, context + "\u0004" + key, key, arguments, 3);
+}
+module.exports = { t: t, tc: tc };
+"#;
+
+impl MessageTable {
+    /// Generate the table as a JSON object keyed by locale and then msgid.
+    ///
+    /// A message is an array of strings and placeholder indices.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push('{');
+        for (i, (locale, messages)) in self.locales.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("\n  ");
+            write_js_string(&mut out, locale);
+            out.push_str(": {");
+            for (j, (key, segments)) in messages.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                out.push_str("\n    ");
+                write_js_string(&mut out, key);
+                out.push_str(": [");
+                for (k, segment) in segments.iter().enumerate() {
+                    if k > 0 {
+                        out.push_str(", ");
+                    }
+                    match segment {
+                        MessageSegment::Text(text) => write_js_string(&mut out, text),
+                        MessageSegment::Placeholder(index) => write!(out, "{}", index).unwrap(),
+                    }
+                }
+                out.push(']');
+            }
+            if !messages.is_empty() {
+                out.push_str("\n  ");
+            }
+            out.push('}');
+        }
+        if !self.locales.is_empty() {
+            out.push('\n');
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Generate a WXS module exporting `t(locale, key, ...args)`.
+    pub fn to_wxs(&self) -> String {
+        format!("var messages = {};{}", self.to_json(), WXS_RUNTIME)
+    }
+}
+
+/// Split a translation into segments with the placeholders of the msgid.
+fn message_segments(translation: &str, placeholders: &[String]) -> Vec<MessageSegment> {
+    split_translated_str(translation.to_string())
+        .into_iter()
+        .map(|item| {
+            let index = placeholder_name(&item)
+                .and_then(|name| placeholders.iter().position(|x| x == name));
+            match index {
+                Some(index) => MessageSegment::Placeholder(index),
+                None => MessageSegment::Text(item),
+            }
+        })
+        .collect()
+}
+
+/// Build the message table of the msgids used in the template.
+pub(super) fn message_table(
+    trans_content: &OrderedTransContent,
+    used_messages: &[(String, Vec<String>)],
+) -> MessageTable {
+    let locales = trans_content
+        .map
+        .iter()
+        .map(|(lang, trans_content_map)| {
+            let messages = used_messages
+                .iter()
+                .filter_map(|(key, placeholders)| {
+                    let translation = trans_content_map.get(key)?;
+                    Some((key.clone(), message_segments(translation, placeholders)))
+                })
+                .collect();
+            (lang.clone(), messages)
+        })
+        .collect();
+    MessageTable { locales }
+}
+
+/// Replace the value with a `t` call of the message module, collecting the msgid into `used_messages`.
+///
+/// The messages selected by the bound values, i.e. the ones with plural forms or ICU `plural` and `select` arguments,
+/// are selected by the locale in the fine-grained way instead.
+fn lookup_value(
+    value: &mut Value,
+    message: &UnitMessage,
+    ctx: LocaleContext,
+    module_name: &str,
+    used_messages: &mut Vec<(String, Vec<String>)>,
+) {
+    let key = message.key();
+    if let Value::Dynamic { .. } = value {
+        let is_selected = ctx.trans_content.map.iter().any(|(_, x)| {
+            (message.plural_count().is_some() && x.plurals.contains_key(&key))
+                || x.icu_messages.contains_key(&key)
+        });
+        if is_selected {
+            // the literals inside are already replaced with `t` calls
+            let options = ExtractOptions {
+                literals: LiteralPolicy::Ignore,
+                ..ctx.options
+            };
+            translate_value_by_locale(value, message, LocaleContext { options, ..ctx });
+            return;
+        }
+    }
+    let msgid = &message.msgid;
+    let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
+    if regex.replace_all(msgid, "").trim().is_empty() {
+        return;
+    }
+    let placeholders = message.expressions();
+    let expression = message_call(message, &placeholders, ctx, module_name, &value.location());
+    set_value_expression(value, expression);
+    use_message(message, placeholders, used_messages);
+}
+
+/// Replace a string literal inside a data binding with a `t` call of the message module.
+fn lookup_literal(
+    expression: &mut Expression,
+    message: &UnitMessage,
+    ctx: LocaleContext,
+    module_name: &str,
+    used_messages: &mut Vec<(String, Vec<String>)>,
+) {
+    *expression = *message_call(message, &[], ctx, module_name, &expression.location());
+    use_message(message, vec![], used_messages);
+}
+
+/// The `t` (or `tc` with a message context) call of a message, with the outer whitespace of the source.
+fn message_call(
+    message: &UnitMessage,
+    placeholders: &[(String, Box<Expression>)],
+    ctx: LocaleContext,
+    module_name: &str,
+    location: &Range<Position>,
+) -> Box<Expression> {
+    let msgid = &message.msgid;
+    let lit_str = |value: &str| Expression::LitStr {
+        value: value.into(),
+        location: location.clone(),
+    };
+    // messages with a context are looked up by `tc` to keep the separator out of the template
+    let (func, mut args) = match &message.context {
+        Some(context) => (
+            "tc",
+            vec![ctx.locale.clone(), lit_str(context), lit_str(msgid)],
+        ),
+        None => ("t", vec![ctx.locale.clone(), lit_str(msgid)]),
+    };
+    args.extend(placeholders.iter().map(|x| (*x.1).clone()));
+    let expression = Box::new(Expression::FuncCall {
+        func: Box::new(Expression::StaticMember {
+            obj: Box::new(Expression::DataField {
+                name: module_name.into(),
+                location: location.clone(),
+            }),
+            field_name: func.into(),
+            dot_location: location.clone(),
+            field_location: location.clone(),
+        }),
+        args,
+        paren_location: (location.clone(), location.clone()),
+    });
+    with_outer_whitespace(expression, message, location)
+}
+
+/// Collect the msgid of a message with the names of its placeholders into `used_messages`.
+fn use_message(
+    message: &UnitMessage,
+    placeholders: Vec<(String, Box<Expression>)>,
+    used_messages: &mut Vec<(String, Vec<String>)>,
+) {
+    let key = message.key();
+    if !used_messages.iter().any(|x| x.0 == key) {
+        used_messages.push((key, placeholders.into_iter().map(|x| x.0).collect()));
+    }
+}
+
+/// Replace the translatable values with `t` (or `tc` with a message context) calls of the message module.
+///
+/// The translate-children groups contain elements, so they are translated in the fine-grained way instead,
+/// including the string literals inside their texts.
+pub(super) fn translate_lookup_table(
+    node_list: &mut [Node],
+    ctx: LocaleContext,
+    module_name: &str,
+    used_messages: &mut Vec<(String, Vec<String>)>,
+) {
+    visit_units(
+        node_list,
+        ctx.options,
+        None,
+        &mut |unit| match unit.target {
+            UnitTarget::Value(value) => {
+                lookup_value(value, &unit.message, ctx, module_name, used_messages);
+                true
+            }
+            UnitTarget::Children(children) => {
+                let context = unit.inherited_context.as_deref();
+                translate_children_by_locale(children, &unit.message, context, ctx);
+                false
+            }
+            // the literals of a group are translated along with the group
+            UnitTarget::Literal(_) if unit.origin == TermOrigin::TranslateChildren => true,
+            UnitTarget::Literal(expression) => {
+                lookup_literal(expression, &unit.message, ctx, module_name, used_messages);
+                true
+            }
+        },
+    );
+}
@@ -1,521 +1,684 @@
-use crate::{has_i18n_translate_children, is_i18n_tag, parse_additional_template};
+mod branches;
+mod fallback;
+mod fine_grained;
+mod icu;
+mod lookup_table;
+mod plural;
+mod template_data;
+mod translate;
+mod validate;
 
-use super::contains_i18n_translate_children;
+pub use fallback::{FallbackUsage, LocaleFallbacks};
+pub use icu::{IcuMessage, MessageFormat};
+pub use lookup_table::{MessageSegment, MessageTable, Messages};
+pub use plural::{PluralMessage, PluralMessages};
+pub use validate::PlaceholderCheck;
+
+use crate::{
+    attribute::parse_attribute_rules,
+    contains_i18n_tag,
+    diagnostic::{collect_parse_warnings, position_of_offset},
+    placeholder::check_element_contexts,
+    po::{msg_key, PoCatalog},
+    visit_units, AttributeRule, Diagnostic, DiagnosticKind, ExtractOptions, LiteralPolicy,
+    PlaceholderStyle, TermOrigin, UnitMessage, WhitespacePolicy,
+};
+
+use branches::translate_template;
+use fallback::{apply_fallbacks, FallbackSources};
+use fine_grained::translate_fine_grained;
 use glass_easel_template_compiler::{
     parse::{
         expr::Expression,
         parse,
-        tag::{Comment, Element, ElementKind, Node, NormalAttribute, Value},
-        Position, TemplateStructure,
+        tag::{Node, Template, Value},
+        Position,
     },
     stringify::{Stringifier, Stringify},
 };
-use regex::Regex;
+use icu::split_icu_messages;
+use lookup_table::{message_table, translate_lookup_table};
+use plural::{plural_forms_rule, resolve_plurals};
 use serde::Deserialize;
-use std::{collections::HashMap, ops::Range};
-use toml;
+use std::{collections::HashMap, ops::Range, str::FromStr};
+use template_data::{forward_locale_data, locale_data_fields};
+use translate::{remove_i18n_meta_tags, remove_i18n_tag, translate};
+use validate::validate_placeholders;
 
 pub struct CompiledTemplate {
     pub output: String,
     pub source_map: Vec<u8>,
+    /// Non-fatal problems found during compilation.
+    pub warnings: Vec<Diagnostic>,
+    /// The messages for the `t` calls in the output, only generated by the lookup-table strategy.
+    pub message_table: Option<MessageTable>,
+    /// The messages of the template translated with fallback locales.
+    pub fallbacks: Vec<FallbackUsage>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The translations keyed by locale and then message key.
+///
+/// It can be built with `TransContent { map, ..Default::default() }` as well as [`TransContent::new`].
+#[derive(Debug, Default, Deserialize)]
 pub struct TransContent {
     #[serde(flatten)]
     pub map: HashMap<String, HashMap<String, String>>,
+    /// The plural translations keyed by locale, which can only be read from PO catalogs.
+    #[serde(skip)]
+    pub plurals: HashMap<String, PluralMessages>,
 }
 
-#[derive(Debug)]
-pub struct OrderedTransContent {
-    pub map: Vec<(String, HashMap<String, String>)>,
-}
-
-pub fn compile(
-    path: &str,
-    source: &str,
-    trans_source: &str,
-    included_attributes: &[String],
-) -> Result<CompiledTemplate, String> {
-    // parse the template
-    let (mut template, parse_state) = parse(path, source);
-    for warning in parse_state.warnings() {
-        if warning.prevent_success() {
-            return Err(format!("Failed to compile template: {}", warning));
+impl TransContent {
+    /// Create the translations from the messages keyed by locale and then msgid.
+    pub fn new(map: HashMap<String, HashMap<String, String>>) -> Self {
+        Self {
+            map,
+            plurals: HashMap::new(),
         }
     }
-    let mut trans_content: TransContent = toml::from_str(&trans_source).unwrap();
-    // merge the global.locale
-    fn merge_map(locale_map: &mut HashMap<String, HashMap<String, String>>) {
-        let mut to_modify = Vec::new();
-        let mut to_remove = Vec::new();
-        for (key, value) in locale_map.iter() {
-            if key.starts_with("global.") {
-                let new_key = key.replace("global.", "");
-                to_remove.push(key.clone());
-                for (msg_id, msg_str) in value.iter() {
-                    to_modify.push((new_key.clone(), msg_id.clone(), msg_str.clone()));
+
+    /// Parse a TOML translation file.
+    ///
+    /// Syntax errors are reported with `path`.
+    pub fn from_toml(path: &str, source: &str) -> Result<Self, Diagnostic> {
+        toml::from_str(source).map_err(|err| {
+            let location = match err.span() {
+                Some(span) => {
+                    position_of_offset(source, span.start)..position_of_offset(source, span.end)
                 }
-            }
-        }
-        for (new_key, msg_id, msg_str) in to_modify {
-            locale_map
-                .entry(new_key)
-                .and_modify(|modify_map| {
-                    modify_map.entry(msg_id.clone()).or_insert(msg_str.clone());
-                })
-                .or_insert_with(|| {
-                    let mut insert_map = HashMap::new();
-                    insert_map.insert(msg_id, msg_str);
-                    insert_map
-                });
-        }
-        for key in to_remove {
-            locale_map.remove(&key);
-        }
+                None => Default::default(),
+            };
+            Diagnostic::new(
+                DiagnosticKind::TranslationFileSyntaxError,
+                path,
+                location,
+                err.message(),
+            )
+        })
     }
-    merge_map(&mut trans_content.map);
-
-    // transform the template to support i18n
-    fn contains_i18n_tag(node_list: &Vec<Node>) -> bool {
-        for node in node_list {
-            match node {
-                Node::UnknownMetaTag(tag, ..) => {
-                    if is_i18n_tag(tag) {
-                        return true;
-                    }
-                    break;
+
+    /// Collect the translations from PO catalogs keyed by locale.
+    ///
+    /// Like the sections in TOML translation files, a `global.` prefix in the locale marks a global catalog.
+    ///
+    /// The entries with `msgid_plural` are also collected as plural messages if all forms are translated,
+    /// selected by the `Plural-Forms` header of the catalog or the CLDR rule of the locale.
+    pub fn from_po_catalogs(catalogs: &[(String, PoCatalog)]) -> Self {
+        let mut map: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut plurals: HashMap<String, PluralMessages> = HashMap::new();
+        for (locale, catalog) in catalogs {
+            let locale_map = map.entry(locale.clone()).or_default();
+            let locale_plurals = plurals.entry(locale.clone()).or_default();
+            if let Some(rule) = catalog
+                .header_value("Plural-Forms")
+                .and_then(plural_forms_rule)
+            {
+                locale_plurals.rule = Some(rule);
+            }
+            for entry in &catalog.entries {
+                if entry.msgid.is_empty() {
+                    continue;
+                }
+                let Some(translation) = entry.translation() else {
+                    continue;
+                };
+                let key = msg_key(entry.msgctxt.as_deref(), &entry.msgid);
+                if entry.msgid_plural.is_some() && entry.msgstr.iter().all(|x| !x.is_empty()) {
+                    locale_plurals
+                        .messages
+                        .insert(key.clone(), entry.msgstr.clone());
                 }
-                _ => {}
+                locale_map.insert(key, translation.to_string());
             }
         }
-        false
+        Self { map, plurals }
     }
 
-    fn remove_i18n_tag(node_list: &Vec<Node>) -> Vec<Node> {
-        let mut new_list = node_list.clone();
-        if let Some(pos) = new_list
+    /// Merge the `global.` locales into the normal ones with a lower priority,
+    /// and then fill the missing translations from the fallback locales, sorted by locale.
+    ///
+    /// Invalid plural rules are reported with `path`.
+    fn resolve(
+        &self,
+        path: &str,
+        fallbacks: &LocaleFallbacks,
+        message_format: MessageFormat,
+    ) -> Result<(OrderedTransContent, FallbackSources), Diagnostic> {
+        let mut map: HashMap<String, HashMap<String, String>> = self
+            .map
             .iter()
-            .position(|node| matches!(node, Node::UnknownMetaTag(tag, ..) if is_i18n_tag(tag)))
-        {
-            new_list.remove(pos);
-        }
-        new_list
-    }
-
-    fn remove_i18n_translate_children(node_list: &mut Vec<Node>) {
-        fn rec(node: &mut Node) {
-            let should_remove = match node {
-                Node::UnknownMetaTag(tag, ..) if has_i18n_translate_children(tag) => true,
-                _ => false,
+            .filter(|(key, _)| !key.starts_with("global."))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        for (key, global_map) in &self.map {
+            let Some(locale) = key.strip_prefix("global.") else {
+                continue;
             };
-            if should_remove {
-                *node = Node::Comment(Comment::new("", node.location()));
-            }
-            if let Node::Element(element) = node {
-                for child in element.iter_children_mut() {
-                    rec(child);
-                }
+            let locale_map = map.entry(locale.to_string()).or_default();
+            for (msg_id, msg_str) in global_map {
+                locale_map
+                    .entry(msg_id.clone())
+                    .or_insert_with(|| msg_str.clone());
             }
         }
-        for node in node_list {
-            rec(node);
-        }
+        let sources = apply_fallbacks(&mut map, fallbacks);
+        let mut plurals = resolve_plurals(path, &self.plurals, fallbacks)?;
+        let mut map: Vec<_> = map
+            .into_iter()
+            .map(|(locale, mut messages)| {
+                let plurals = plurals.remove(&locale).unwrap_or_default();
+                let (icu_messages, invalid) = match message_format {
+                    MessageFormat::Plain => Default::default(),
+                    MessageFormat::Icu => {
+                        split_icu_messages(&locale, &mut messages, sources.get(&locale))
+                    }
+                };
+                let locale_messages = LocaleMessages {
+                    messages,
+                    plurals,
+                    icu_messages,
+                    invalid,
+                };
+                (locale, locale_messages)
+            })
+            .collect();
+        map.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok((OrderedTransContent { map }, sources))
     }
+}
 
-    fn split_translated_str(translated_str: String) -> Vec<String> {
-        let mut translated_str_vec: Vec<String> = Vec::new();
-        let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
-        let mut last = 0;
-        for mat in regex.find_iter(&translated_str) {
-            if mat.start() != last {
-                translated_str_vec.push(translated_str[last..mat.start()].to_string());
-            }
-            translated_str_vec.push(translated_str[mat.start()..mat.end()].to_string());
-            last = mat.end();
-        }
-        if last < translated_str.len() {
-            translated_str_vec.push(translated_str[last..].to_string());
-        }
-        translated_str_vec
+/// The translations of a single locale.
+#[derive(Debug, Default)]
+pub struct LocaleMessages {
+    pub messages: HashMap<String, String>,
+    /// The plural forms of the messages which have them.
+    pub plurals: HashMap<String, PluralMessage>,
+    /// The translations with `plural` or `select` arguments, if they are in ICU MessageFormat.
+    pub icu_messages: HashMap<String, IcuMessage>,
+    /// The errors of the translations which cannot be parsed in the message format, keyed by msgid.
+    pub invalid: HashMap<String, String>,
+}
+
+impl LocaleMessages {
+    /// Get the translation of a message, which is the first form of a plural message.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.messages.get(key)
     }
+}
 
-    fn translate_value(value: &mut Value, trans_content_map: &HashMap<String, String>) {
-        match value {
-            Value::Static { ref mut value, .. } => {
-                if let Some(translation) = trans_content_map.get(&value.to_string()) {
-                    *value = translation.into();
-                }
-            }
-            Value::Dynamic {
-                ref mut expression,
-                double_brace_location,
-                ..
-            } => {
-                fn split_expression(
-                    expr: &Expression,
-                    expr_vec: &mut Vec<String>,
-                    placeholder_map: &mut HashMap<char, Box<Expression>>,
-                    placehoder_char: &mut char,
-                    start_location: &Range<Position>,
-                    end_location: &Range<Position>,
-                ) {
-                    match expr {
-                        Expression::LitStr { value, .. } => expr_vec.push(value.to_string()),
-                        Expression::ToStringWithoutUndefined { value, .. } => {
-                            expr_vec.push(format!("{{{{{}}}}}", placehoder_char));
-                            placeholder_map.insert(placehoder_char.clone(), value.clone());
-                            *placehoder_char = ((*placehoder_char as u8) + 1) as char;
-                        }
-                        Expression::Plus {
-                            left,
-                            right,
-                            location,
-                        } => {
-                            let split = if let Expression::ToStringWithoutUndefined { .. }
-                            | Expression::LitStr { .. } = &**left
-                            {
-                                true
-                            } else if let Expression::ToStringWithoutUndefined { .. }
-                            | Expression::LitStr { .. } = &**right
-                            {
-                                true
-                            } else {
-                                false
-                            };
-                            if split {
-                                split_expression(
-                                    &left,
-                                    expr_vec,
-                                    placeholder_map,
-                                    placehoder_char,
-                                    start_location,
-                                    location,
-                                );
-                                split_expression(
-                                    &right,
-                                    expr_vec,
-                                    placeholder_map,
-                                    placehoder_char,
-                                    location,
-                                    end_location,
-                                );
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                let mut expr_vec: Vec<String> = Vec::new();
-                let mut placeholder_map: HashMap<char, Box<Expression>> = HashMap::new();
-                let mut start_placeholder = 'A';
-                split_expression(
-                    &expression,
-                    &mut expr_vec,
-                    &mut placeholder_map,
-                    &mut start_placeholder,
-                    &double_brace_location.0,
-                    &double_brace_location.1,
-                );
-                let mut expr_str = expr_vec.join("");
-                if let Some(translation) = trans_content_map.get(&expr_str) {
-                    expr_str = translation.clone();
-                    let translated_expr_vec: Vec<String> = split_translated_str(expr_str);
-                    fn get_expr(
-                        regex: &Regex,
-                        item: &String,
-                        position: &Range<Position>,
-                        placeholder_map: &HashMap<char, Box<Expression>>,
-                    ) -> Box<Expression> {
-                        let trimed_item = item.trim_matches(|c| c == '{' || c == '}');
-                        let potential_placeholder = trimed_item.chars().next().unwrap();
-                        if regex.is_match(item)
-                            && trimed_item.len() == 1
-                            && placeholder_map.contains_key(&potential_placeholder)
-                        {
-                            return Box::new(Expression::ToStringWithoutUndefined {
-                                value: placeholder_map.get(&potential_placeholder).unwrap().clone(),
-                                location: position.clone(),
-                            });
-                        } else {
-                            Box::new(Expression::LitStr {
-                                value: item.into(),
-                                location: position.clone(),
-                            })
-                        }
-                    }
-                    let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
-                    let translated_expression = translated_expr_vec
-                        .into_iter()
-                        .map(|item| {
-                            get_expr(&regex, &item, &double_brace_location.0, &placeholder_map)
-                        })
-                        .fold(None, |acc, x| match acc {
-                            None => Some(x),
-                            Some(acc) => Some(Box::new(Expression::Plus {
-                                left: acc,
-                                right: x,
-                                location: double_brace_location.clone().0,
-                            })),
-                        })
-                        .unwrap();
-                    *expression = translated_expression;
-                }
-            }
-            _ => {}
+#[derive(Debug)]
+pub struct OrderedTransContent {
+    pub map: Vec<(String, LocaleMessages)>,
+}
+
+/// The state shared by the strategies which select the translation by the locale at runtime.
+#[derive(Clone, Copy)]
+struct LocaleContext<'a> {
+    trans_content: &'a OrderedTransContent,
+    options: ExtractOptions<'a>,
+    /// The expression of the current locale.
+    locale: &'a Expression,
+    /// The locales already selected by an enclosing translate-children group, which are left out.
+    skipped_locales: &'a [&'a str],
+}
+
+impl<'a> LocaleContext<'a> {
+    /// The translations of the locales which are not skipped.
+    fn locale_messages(self) -> impl DoubleEndedIterator<Item = &'a (String, LocaleMessages)> {
+        self.trans_content
+            .map
+            .iter()
+            .filter(move |(lang, _)| !self.skipped_locales.contains(&lang.as_str()))
+    }
+}
+
+/// Parse a single glass-easel expression.
+fn parse_expression(expression: &str) -> Result<Expression, String> {
+    let src = format!("{{{{{}}}}}", expression);
+    let (mut template, parse_state) = parse("", &src);
+    collect_parse_warnings(parse_state.warnings()).map_err(|err| err.message)?;
+    let Some(Node::Text(Value::Dynamic { expression, .. })) = template.content.pop() else {
+        return Err("not an expression".to_string());
+    };
+    // several data bindings are joined with `ToStringWithoutUndefined`
+    fn is_single(expr: &Expression) -> bool {
+        !matches!(expr, Expression::ToStringWithoutUndefined { .. })
+            && expr.sub_expressions().all(is_single)
+    }
+    if !template.content.is_empty() || !is_single(&expression) {
+        return Err("not a single expression".to_string());
+    }
+    Ok(*expression)
+}
+
+/// Parse the `locale_expression` option.
+fn parse_locale_expression(path: &str, expression: &str) -> Result<Expression, Diagnostic> {
+    parse_expression(expression).map_err(|message| {
+        Diagnostic::without_location(
+            DiagnosticKind::InvalidOption,
+            path,
+            format!("Invalid locale expression {:?}: {}", expression, message),
+        )
+    })
+}
+
+/// How the translations of all locales are combined into a single output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompileStrategy {
+    /// Wrap a translated copy of the whole template for each locale in a `wx:if` chain.
+    #[default]
+    Branches,
+    /// Keep the template structure and only select the translation by the locale
+    /// at the text nodes, attributes and translate-children groups which have translations.
+    FineGrained,
+    /// Replace the text nodes and attributes with `t` calls of a WXS module,
+    /// so that the translations are looked up from the [`MessageTable`] at runtime.
+    ///
+    /// The translate-children groups contain elements, so they are translated like [`Self::FineGrained`].
+    LookupTable,
+}
+
+impl FromStr for CompileStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "branches" => Ok(Self::Branches),
+            "fine-grained" => Ok(Self::FineGrained),
+            "lookup-table" => Ok(Self::LookupTable),
+            _ => Err(format!("Unknown compile strategy: {}", s)),
         }
     }
+}
 
-    fn translate_option_value(
-        value: &mut Option<Value>,
-        trans_content_map: &HashMap<String, String>,
-    ) {
-        if let Some(value) = value {
-            translate_value(value, trans_content_map);
+/// Options of the compilation.
+///
+/// The options deciding the msgids, i.e. `included_attributes`, `placeholder_style`, `whitespace` and `literals`,
+/// should be the same as the ones used to extract the terms.
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// The rules of the attributes to translate besides the text nodes, see [`AttributeRule`].
+    pub included_attributes: Vec<String>,
+    /// Ignored by [`compile_per_locale`].
+    pub strategy: CompileStrategy,
+    /// The name of the WXS module used by the lookup-table strategy, `i18n` by default.
+    pub message_module_name: String,
+    /// The `src` of the WXS module used by the lookup-table strategy.
+    ///
+    /// If given, a `<wxs>` tag is appended to the output unless the template already declares the module.
+    pub message_module_src: Option<String>,
+    /// The fallback chains used for the missing translations.
+    pub locale_fallbacks: LocaleFallbacks,
+    /// The expression of the current locale, `locale` by default.
+    ///
+    /// It can be any glass-easel expression, e.g. a data field path like `app.settings.lang`.
+    /// Ignored by [`compile_per_locale`].
+    pub locale_expression: String,
+    /// Add the data fields read by the locale expression to the `data` of `<template is>` call sites
+    /// which do not pass them, `true` by default.
+    ///
+    /// If disabled, these call sites are reported as warnings instead.
+    /// Ignored by [`compile_per_locale`].
+    pub forward_locale: bool,
+    /// How the placeholders in msgids are named.
+    pub placeholder_style: PlaceholderStyle,
+    /// How the translations with mismatched placeholders are reported, as warnings by default.
+    pub placeholder_check: PlaceholderCheck,
+    /// The syntax of the translations, plain text with placeholders by default.
+    pub message_format: MessageFormat,
+    /// How the whitespace of texts is normalized into msgids.
+    pub whitespace: WhitespacePolicy,
+    /// Which string literals inside data bindings are translated.
+    pub literals: LiteralPolicy,
+}
+
+impl CompileOptions {
+    /// The options of finding the translatable units with the parsed attribute rules,
+    /// which finds the same units as compiling when passed to [`visit_units`].
+    pub fn extract_options<'a>(&self, attributes: &'a [AttributeRule]) -> ExtractOptions<'a> {
+        ExtractOptions {
+            attributes,
+            placeholder_style: self.placeholder_style,
+            whitespace: self.whitespace,
+            literals: self.literals,
+            literals_with_messages: false,
         }
     }
+}
 
-    fn translate_attribute(
-        attributes: &mut Vec<NormalAttribute>,
-        trans_content_map: &HashMap<String, String>,
-        included_attributes: &[String],
-    ) {
-        for attribute in attributes {
-            if included_attributes.contains(&attribute.name.name.to_string()) {
-                translate_option_value(&mut attribute.value, trans_content_map)
-            }
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            included_attributes: vec![],
+            strategy: CompileStrategy::default(),
+            message_module_name: "i18n".to_string(),
+            message_module_src: None,
+            locale_fallbacks: LocaleFallbacks::default(),
+            locale_expression: "locale".to_string(),
+            forward_locale: true,
+            placeholder_style: PlaceholderStyle::default(),
+            placeholder_check: PlaceholderCheck::default(),
+            message_format: MessageFormat::default(),
+            whitespace: WhitespacePolicy::default(),
+            literals: LiteralPolicy::default(),
         }
     }
+}
+
+/// The outputs of [`compile_per_locale`].
+pub struct CompiledLocales {
+    /// The translated template of each locale, sorted by locale.
+    pub locales: Vec<(String, CompiledTemplate)>,
+    /// The template in the source language, for the locales without translations.
+    pub fallback: CompiledTemplate,
+    /// Non-fatal problems found in the template.
+    pub warnings: Vec<Diagnostic>,
+}
 
-    fn translate_entire_children(
-        node_list: &mut Vec<Node>,
-        trans_content_map: &HashMap<String, String>,
-    ) {
-        if let Some(pos) = node_list.iter().position(
-            |node| matches!(node, Node::UnknownMetaTag(tag, ..) if has_i18n_translate_children(tag)),
-        ) {
-            node_list.remove(pos);
+/// Compile the template with a TOML translation file.
+///
+/// Since the path of the TOML file is unknown here, its syntax errors are reported with the template `path`.
+pub fn compile(
+    path: &str,
+    source: &str,
+    trans_source: &str,
+    included_attributes: &[String],
+) -> Result<CompiledTemplate, Diagnostic> {
+    let trans_content = TransContent::from_toml(path, trans_source)?;
+    let options = CompileOptions {
+        included_attributes: included_attributes.to_vec(),
+        ..Default::default()
+    };
+    compile_with_options(path, source, &trans_content, &options)
+}
+
+/// Compile the template with PO catalogs keyed by locale.
+///
+/// Catalogs with a `global.` prefixed locale are merged into the others with a lower priority.
+pub fn compile_po(
+    path: &str,
+    source: &str,
+    catalogs: &[(String, PoCatalog)],
+    included_attributes: &[String],
+) -> Result<CompiledTemplate, Diagnostic> {
+    let trans_content = TransContent::from_po_catalogs(catalogs);
+    let options = CompileOptions {
+        included_attributes: included_attributes.to_vec(),
+        ..Default::default()
+    };
+    compile_with_options(path, source, &trans_content, &options)
+}
+
+fn stringify_template(
+    path: &str,
+    source: &str,
+    template: &Template,
+    warnings: Vec<Diagnostic>,
+) -> Result<CompiledTemplate, Diagnostic> {
+    let output_err = || {
+        Diagnostic::without_location(DiagnosticKind::OutputError, path, "Failed to write output")
+    };
+    let mut stringifier = Stringifier::new(String::new(), path, source);
+    template
+        .stringify_write(&mut stringifier)
+        .map_err(|_| output_err())?;
+    let (output, sm) = stringifier.finish();
+    let mut source_map = vec![];
+    sm.to_writer(&mut source_map).map_err(|_| output_err())?;
+    Ok(CompiledTemplate {
+        output,
+        source_map,
+        warnings,
+        message_table: None,
+        fallbacks: vec![],
+    })
+}
+
+/// A distinct message of the units visited by the translation pass.
+struct VisitedMessage {
+    message: UnitMessage,
+    /// The location of the first unit.
+    location: Range<Position>,
+    /// Whether any unit is a translate-children group.
+    is_group: bool,
+}
+
+/// Collect the distinct messages of the translatable units in the nodes, in source order.
+///
+/// The units are found in the same way as the translation pass does, including the ones inside translate-children groups.
+fn collect_messages(
+    node_list: &mut [Node],
+    options: ExtractOptions,
+    messages: &mut Vec<VisitedMessage>,
+) {
+    visit_units(node_list, options, None, &mut |unit| {
+        let key = unit.message.key();
+        let is_group = unit.origin == TermOrigin::TranslateChildren;
+        match messages.iter_mut().find(|x| x.message.key() == key) {
+            Some(x) => x.is_group |= is_group,
+            None => messages.push(VisitedMessage {
+                message: unit.message,
+                location: unit.location,
+                is_group,
+            }),
         }
-        let mut text_vec: Vec<String> = Vec::new();
-        let mut placehoder_char = 'A';
-        let mut placeholder_map: HashMap<char, Node> = HashMap::new();
-        let mut first_text_node: Option<Node> = None;
-        for node in node_list.into_iter() {
-            match node {
-                Node::Text(value) => {
-                    if let Value::Static { value, .. } = value {
-                        text_vec.push(value.trim().to_string());
-                        if let None = first_text_node {
-                            first_text_node = Some((*node).clone());
-                        }
-                    }
-                }
-                _ => {
-                    text_vec.push(format!("{{{{{}}}}}", placehoder_char));
-                    placeholder_map.insert(placehoder_char.clone(), node.clone());
-                    placehoder_char = ((placehoder_char as u8) + 1) as char;
-                }
-            }
+        true
+    });
+}
+
+/// Find the messages of the template which are translated with fallback locales.
+fn fallback_usages(
+    messages: &[VisitedMessage],
+    fallback_sources: &FallbackSources,
+    locales: &[&str],
+) -> Vec<FallbackUsage> {
+    let mut ret = vec![];
+    for VisitedMessage { message, .. } in messages {
+        for locale in locales {
+            let Some(fallback) = fallback_sources
+                .get(*locale)
+                .and_then(|x| x.get(&message.key()))
+            else {
+                continue;
+            };
+            ret.push(FallbackUsage {
+                locale: locale.to_string(),
+                msgid: message.msgid.clone(),
+                fallback: fallback.clone(),
+            });
         }
-        let Some(first_text_node) = first_text_node else {
-            return;
-        };
-        let mut text_str = text_vec.join("");
-        if let Some(translation) = trans_content_map.get(&text_str) {
-            text_str = translation.clone();
-            let translated_text_vec = split_translated_str(text_str);
-            let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
-            let mut new_node_list: Vec<Node> = Vec::new();
-            for item in translated_text_vec {
-                let trimed_item = item.trim_matches(|c| c == '{' || c == '}');
-                let potential_placeholder = trimed_item.chars().next().unwrap();
-                if regex.is_match(&item)
-                    && trimed_item.len() == 1
-                    && placeholder_map.contains_key(&potential_placeholder)
-                {
-                    new_node_list
-                        .push(placeholder_map.get(&potential_placeholder).unwrap().clone());
-                } else {
-                    let mut static_text = first_text_node.clone();
-                    let Node::Text(Value::Static { ref mut value, .. }) = static_text else {
-                        unreachable!()
-                    };
-                    *value = item.into();
-                    new_node_list.push(static_text);
-                }
+    }
+    ret
+}
+
+/// Check the placeholders of the translations of the visited messages.
+fn check_messages(
+    path: &str,
+    messages: &[VisitedMessage],
+    options: &CompileOptions,
+    trans_content: &OrderedTransContent,
+    fallback_sources: &FallbackSources,
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<(), Diagnostic> {
+    let mismatches = validate_placeholders(path, messages, trans_content, fallback_sources);
+    match options.placeholder_check {
+        PlaceholderCheck::Warning => warnings.extend(mismatches),
+        PlaceholderCheck::Error => {
+            if let Some(err) = mismatches.into_iter().next() {
+                return Err(err);
             }
-            *node_list = new_node_list;
         }
     }
+    Ok(())
+}
 
-    // the Position of else_branch or branches just placed by the position of the template's first child
-    fn get_first_child_position(template: &Vec<Node>) -> Option<Range<Position>> {
-        let position: Option<Range<Position>>;
-        match template.get(0)? {
-            Node::Element(element) => {
-                position = Some(element.tag_location.close.clone());
-            }
-            Node::Text(value) => match value {
-                Value::Dynamic {
-                    double_brace_location,
-                    ..
-                } => {
-                    let (first_location, _) = double_brace_location;
-                    position = Some(first_location.clone());
+/// Compile the template into a single output which selects the translation by the `locale` data field.
+pub fn compile_with_options(
+    path: &str,
+    source: &str,
+    trans_content: &TransContent,
+    options: &CompileOptions,
+) -> Result<CompiledTemplate, Diagnostic> {
+    // parse the template
+    let (mut template, parse_state) = parse(path, source);
+    let mut warnings = collect_parse_warnings(parse_state.warnings())?;
+    // merge the global.locale and the fallbacks
+    let (trans_content, fallback_sources) =
+        trans_content.resolve(path, &options.locale_fallbacks, options.message_format)?;
+    let locale = parse_locale_expression(path, &options.locale_expression)?;
+    let attributes = parse_attribute_rules(path, &options.included_attributes)?;
+    let ctx = LocaleContext {
+        trans_content: &trans_content,
+        options: options.extract_options(&attributes),
+        locale: &locale,
+        skipped_locales: &[],
+    };
+    let mut used_messages = vec![];
+    let mut messages = vec![];
+    let is_i18n = contains_i18n_tag(&template.content);
+
+    if is_i18n {
+        template.content = remove_i18n_tag(&template.content);
+        let mut node_lists = vec![&mut template.content];
+        for sub_template in &mut template.globals.sub_templates {
+            node_lists.push(&mut sub_template.content);
+        }
+        let locale_fields = locale_data_fields(&locale);
+        for node_list in node_lists {
+            // sub-templates read the locale from their own data
+            forward_locale_data(
+                path,
+                node_list,
+                &locale_fields,
+                options.forward_locale,
+                &mut warnings,
+            );
+            check_element_contexts(path, node_list, &mut warnings);
+            collect_messages(node_list, ctx.options, &mut messages);
+            match options.strategy {
+                CompileStrategy::Branches => {
+                    let i18n_block = translate_template(node_list.clone(), ctx);
+                    *node_list = vec![Node::Element(i18n_block)];
                 }
-                Value::Static { location, .. } => {
-                    position = Some(location.clone());
+                CompileStrategy::FineGrained => {
+                    translate_fine_grained(node_list, ctx);
                 }
-                _ => {
-                    return None;
+                CompileStrategy::LookupTable => {
+                    translate_lookup_table(
+                        node_list,
+                        ctx,
+                        &options.message_module_name,
+                        &mut used_messages,
+                    );
                 }
-            },
-            Node::Comment(x) => {
-                position = Some(x.location.clone());
-            }
-            Node::UnknownMetaTag(x) => {
-                position = Some(x.location.clone());
-            }
-            _ => {
-                return None;
             }
+            remove_i18n_meta_tags(node_list);
         }
-        position
     }
+    check_messages(
+        path,
+        &messages,
+        options,
+        &trans_content,
+        &fallback_sources,
+        &mut warnings,
+    )?;
 
-    fn translate(
-        node_list: &mut Vec<Node>,
-        trans_content_map: &HashMap<String, String>,
-        included_attributes: &[String],
-    ) {
-        for node in node_list {
-            match node {
-                Node::Element(element) => match &mut element.kind {
-                    ElementKind::Normal {
-                        children,
-                        attributes,
-                        ..
-                    } => {
-                        if attributes.len() != 0 {
-                            translate_attribute(attributes, trans_content_map, included_attributes);
-                        }
-                        if contains_i18n_translate_children(children) {
-                            translate_entire_children(children, trans_content_map);
-                        }
-                        translate(children, trans_content_map, included_attributes);
-                    }
-                    ElementKind::If {
-                        branches,
-                        else_branch,
-                        ..
-                    } => {
-                        for branch in branches {
-                            translate(&mut branch.2, trans_content_map, included_attributes)
-                        }
-                        match else_branch {
-                            Some((_, ref mut nodes)) => {
-                                translate(nodes, trans_content_map, included_attributes)
-                            }
-                            _ => {}
-                        }
-                    }
-                    ElementKind::For { children, .. } => {
-                        translate(children, trans_content_map, included_attributes)
-                    }
-                    ElementKind::Pure { children, .. } => {
-                        translate(children, trans_content_map, included_attributes)
-                    }
-                    _ => {}
-                },
-                Node::Text(value) => {
-                    translate_value(value, trans_content_map);
-                }
-                _ => {}
-            }
+    let mut compiled = stringify_template(path, source, &template, warnings)?;
+    let locales: Vec<&str> = trans_content.map.iter().map(|x| x.0.as_str()).collect();
+    compiled.fallbacks = fallback_usages(&messages, &fallback_sources, &locales);
+    if options.strategy == CompileStrategy::LookupTable {
+        compiled.message_table = Some(message_table(&trans_content, &used_messages));
+        let declared = template
+            .globals
+            .scripts
+            .iter()
+            .any(|x| x.module_name().name.as_str() == options.message_module_name);
+        if let (Some(src), false) = (&options.message_module_src, declared) {
+            // WXS modules are file-global, so appending the tag does not affect the source map
+            compiled.output.push_str(&format!(
+                r#"<wxs module="{}" src="{}"/>"#,
+                options.message_module_name,
+                src.replace('&', "&amp;").replace('"', "&quot;"),
+            ));
         }
     }
-    fn translate_template(
-        template: Vec<Node>,
-        trans_content: &OrderedTransContent,
-        included_attributes: &[String],
-    ) -> Element {
-        // generate branch content
-        let mut branches: Vec<(Range<Position>, Value, Vec<Node>)> = vec![];
-        let branch_position = get_first_child_position(&template).unwrap_or_default();
-        for (lang, trans_content_map) in trans_content.map.iter() {
-            let mut template_item = template.clone();
-            let eq_full = Box::new(Expression::EqFull {
-                left: Box::new(Expression::DataField {
-                    name: "locale".into(),
-                    location: branch_position.clone(),
-                }),
-                right: Box::new(Expression::LitStr {
-                    value: lang.into(),
-                    location: branch_position.clone(),
-                }),
-                location: branch_position.clone(),
-            });
-            let branch_value =
-                Value::new_expression(eq_full, (branch_position.clone(), branch_position.clone()));
-            translate(&mut template_item, trans_content_map, included_attributes);
-            branches.push((branch_position.clone(), branch_value, template_item));
+    Ok(compiled)
+}
+
+/// Compile the template into a separate output for each locale.
+///
+/// Unlike [`compile_with_options`], each output only contains the markup of a single locale,
+/// so the size of it does not grow with the number of locales.
+/// The `fallback` output keeps the source language for the locales without translations.
+pub fn compile_per_locale(
+    path: &str,
+    source: &str,
+    trans_content: &TransContent,
+    options: &CompileOptions,
+) -> Result<CompiledLocales, Diagnostic> {
+    // parse the template
+    let (mut template, parse_state) = parse(path, source);
+    let mut warnings = collect_parse_warnings(parse_state.warnings())?;
+    let (trans_content, fallback_sources) =
+        trans_content.resolve(path, &options.locale_fallbacks, options.message_format)?;
+    let attributes = parse_attribute_rules(path, &options.included_attributes)?;
+    let is_i18n = contains_i18n_tag(&template.content);
+    let mut messages = vec![];
+    if is_i18n {
+        template.content = remove_i18n_tag(&template.content);
+        let extract_options = options.extract_options(&attributes);
+        check_element_contexts(path, &template.content, &mut warnings);
+        collect_messages(&mut template.content, extract_options, &mut messages);
+        for sub_template in &mut template.globals.sub_templates {
+            check_element_contexts(path, &sub_template.content, &mut warnings);
+            collect_messages(&mut sub_template.content, extract_options, &mut messages);
         }
-        let mut else_branch_template = template.clone();
-        remove_i18n_translate_children(&mut else_branch_template);
-        let else_branch = Some((branch_position.clone(), else_branch_template));
-
-        // generate a new node
-        let mut if_block_template =
-            parse_additional_template(r#"<block wx:if="" /><block wx:else="" />"#);
-        let Node::Element(mut if_block) = if_block_template.content.pop().unwrap() else {
-            panic!()
-        };
-        if_block.tag_location.start = (branch_position.clone(), branch_position.clone());
-        if_block.tag_location.close = branch_position.clone();
-        if_block.tag_location.end = Some((branch_position.clone(), branch_position.clone()));
-        let ElementKind::If {
-            branches: new_branches,
-            else_branch: new_else_branch,
-            ..
-        } = &mut if_block.kind
-        else {
-            panic!()
-        };
-        *new_branches = branches;
-        *new_else_branch = else_branch;
-
-        if_block
     }
+    check_messages(
+        path,
+        &messages,
+        options,
+        &trans_content,
+        &fallback_sources,
+        &mut warnings,
+    )?;
 
-    if contains_i18n_tag(&template.content) {
-        let mut trans_content = OrderedTransContent {
-            map: trans_content.map.into_iter().collect(),
-        };
-        trans_content.map.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-
-        // template.content
-        let branch_template = remove_i18n_tag(&template.content);
-        let template_content_i18n =
-            translate_template(branch_template, &trans_content, &included_attributes);
-        template.content = vec![Node::Element(template_content_i18n)];
+    let mut locales = vec![];
+    for (locale, trans_content_map) in &trans_content.map {
+        let mut locale_template = template.clone();
+        if is_i18n {
+            translate(
+                &mut locale_template.content,
+                trans_content_map,
+                options.extract_options(&attributes),
+            );
+            remove_i18n_meta_tags(&mut locale_template.content);
+            for sub_template in &mut locale_template.globals.sub_templates {
+                translate(
+                    &mut sub_template.content,
+                    trans_content_map,
+                    options.extract_options(&attributes),
+                );
+                remove_i18n_meta_tags(&mut sub_template.content);
+            }
+        }
+        let mut compiled = stringify_template(path, source, &locale_template, vec![])?;
+        compiled.fallbacks = fallback_usages(&messages, &fallback_sources, &[locale]);
+        locales.push((locale.clone(), compiled));
+    }
 
-        // sub_templates
+    if is_i18n {
+        remove_i18n_meta_tags(&mut template.content);
         for sub_template in &mut template.globals.sub_templates {
-            // search_terms(&sub_template.1, &mut output, &included_attributes);
-            let sub_template_branch = sub_template.content.clone();
-            let sub_template_i18n =
-                translate_template(sub_template_branch, &trans_content, &included_attributes);
-            sub_template.content = vec![Node::Element(sub_template_i18n)]
+            remove_i18n_meta_tags(&mut sub_template.content);
         }
     }
+    let fallback = stringify_template(path, source, &template, vec![])?;
 
-    // stringify the template
-    let mut stringifier = Stringifier::new(String::new(), path, source);
-    template
-        .stringify_write(&mut stringifier)
-        .map_err(|_| "Failed to write output")?;
-    let (output, sm) = stringifier.finish();
-    let mut source_map = vec![];
-    sm.to_writer(&mut source_map)
-        .map_err(|_| "Failed to write output")?;
-    Ok(CompiledTemplate { output, source_map })
+    Ok(CompiledLocales {
+        locales,
+        fallback,
+        warnings,
+    })
 }
@@ -0,0 +1,230 @@
+use super::{fallback::apply_fallbacks, parse_expression, LocaleFallbacks};
+use crate::{Diagnostic, DiagnosticKind};
+use glass_easel_template_compiler::parse::{expr::Expression, Position};
+use std::{collections::HashMap, ops::Range};
+
+/// The plural translations of a catalog, read from the entries with `msgid_plural`.
+#[derive(Debug, Clone, Default)]
+pub struct PluralMessages {
+    /// The `plural=` expression of the `Plural-Forms` header, if given.
+    ///
+    /// The CLDR rule of the locale is used otherwise.
+    pub rule: Option<String>,
+    /// The translation of each plural form keyed by msgid.
+    pub messages: HashMap<String, Vec<String>>,
+}
+
+/// The plural forms of a message with the rule selecting them.
+#[derive(Debug, Clone)]
+pub struct PluralMessage {
+    /// The expression of the form index, in which `n` is the count.
+    pub rule: Expression,
+    pub forms: Vec<String>,
+}
+
+/// The `plural=` expression of a `Plural-Forms` header value.
+pub(super) fn plural_forms_rule(plural_forms: &str) -> Option<String> {
+    let (_, rule) = plural_forms.split_once("plural=")?;
+    let rule = rule.trim().trim_end_matches(';').trim();
+    if rule.is_empty() {
+        return None;
+    }
+    Some(rule.to_string())
+}
+
+/// The plural rule of the locale in gettext syntax, derived from the CLDR plural rules of integers.
+pub(super) fn default_plural_rule(locale: &str) -> &'static str {
+    let locale = locale.to_ascii_lowercase().replace('_', "-");
+    let language = locale.split('-').next().unwrap_or_default();
+    match (locale.as_str(), language) {
+        ("pt-br", _) => "n > 1",
+        (_, "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "lo" | "my") => "0",
+        (_, "fr" | "hy" | "kab") => "n > 1",
+        (_, "ru" | "uk" | "be" | "sr" | "hr" | "bs") => {
+            "n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2"
+        }
+        (_, "pl") => "n==1 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2",
+        (_, "cs" | "sk") => "n==1 ? 0 : n>=2 && n<=4 ? 1 : 2",
+        (_, "lt") => "n%10==1 && n%100!=11 ? 0 : n%10>=2 && (n%100<10 || n%100>=20) ? 1 : 2",
+        (_, "sl") => "n%100==1 ? 0 : n%100==2 ? 1 : n%100==3 || n%100==4 ? 2 : 3",
+        (_, "ar") => {
+            "n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n%100>=3 && n%100<=10 ? 3 : n%100>=11 ? 4 : 5"
+        }
+        (_, "lv") => "n%10==0 || n%100>=11 && n%100<=19 ? 0 : n%10==1 && n%100!=11 ? 1 : 2",
+        (_, "ro") => "n==1 ? 0 : n==0 || n%100>=1 && n%100<=19 ? 1 : 2",
+        (_, "he") => "n==1 ? 0 : n==2 ? 1 : 2",
+        (_, "ga") => "n==1 ? 0 : n==2 ? 1 : n>=3 && n<=6 ? 2 : n>=7 && n<=10 ? 3 : 4",
+        (_, "cy") => "n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n==3 ? 3 : n==6 ? 4 : 5",
+        _ => "n != 1",
+    }
+}
+
+/// The CLDR plural categories of the locale with their conditions over integers, except `other`.
+pub(super) fn plural_categories(locale: &str) -> &'static [(&'static str, &'static str)] {
+    let locale = locale.to_ascii_lowercase().replace('_', "-");
+    let language = locale.split('-').next().unwrap_or_default();
+    match (locale.as_str(), language) {
+        ("pt-br", _) => &[("one", "n==0 || n==1")],
+        (_, "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "lo" | "my") => &[],
+        (_, "fr" | "hy" | "kab") => &[("one", "n==0 || n==1")],
+        (_, "ru" | "uk" | "be") => &[
+            ("one", "n%10==1 && n%100!=11"),
+            ("few", "n%10>=2 && n%10<=4 && (n%100<12 || n%100>14)"),
+            ("many", "n%10==0 || n%10>=5 || n%100>=11 && n%100<=14"),
+        ],
+        (_, "sr" | "hr" | "bs") => &[
+            ("one", "n%10==1 && n%100!=11"),
+            ("few", "n%10>=2 && n%10<=4 && (n%100<12 || n%100>14)"),
+        ],
+        (_, "pl") => &[
+            ("one", "n==1"),
+            ("few", "n%10>=2 && n%10<=4 && (n%100<12 || n%100>14)"),
+            (
+                "many",
+                "n!=1 && n%10<=1 || n%10>=5 || n%100>=12 && n%100<=14",
+            ),
+        ],
+        (_, "cs" | "sk") => &[("one", "n==1"), ("few", "n>=2 && n<=4")],
+        (_, "lt") => &[
+            ("one", "n%10==1 && (n%100<11 || n%100>19)"),
+            ("few", "n%10>=2 && (n%100<11 || n%100>19)"),
+        ],
+        (_, "sl") => &[
+            ("one", "n%100==1"),
+            ("two", "n%100==2"),
+            ("few", "n%100==3 || n%100==4"),
+        ],
+        (_, "ar") => &[
+            ("zero", "n==0"),
+            ("one", "n==1"),
+            ("two", "n==2"),
+            ("few", "n%100>=3 && n%100<=10"),
+            ("many", "n%100>=11"),
+        ],
+        (_, "lv") => &[
+            ("zero", "n%10==0 || n%100>=11 && n%100<=19"),
+            ("one", "n%10==1 && n%100!=11"),
+        ],
+        (_, "ro") => &[
+            ("one", "n==1"),
+            ("few", "n==0 || n!=1 && n%100>=1 && n%100<=19"),
+        ],
+        (_, "he") => &[("one", "n==1"), ("two", "n==2")],
+        (_, "ga") => &[
+            ("one", "n==1"),
+            ("two", "n==2"),
+            ("few", "n>=3 && n<=6"),
+            ("many", "n>=7 && n<=10"),
+        ],
+        (_, "cy") => &[
+            ("zero", "n==0"),
+            ("one", "n==1"),
+            ("two", "n==2"),
+            ("few", "n==3"),
+            ("many", "n==6"),
+        ],
+        _ => &[("one", "n==1")],
+    }
+}
+
+/// Replace the `n` in a plural rule with the count.
+pub(super) fn substitute_count(rule: &Expression, count: &Expression) -> Expression {
+    fn rec(expr: &mut Expression, count: &Expression) {
+        if matches!(expr, Expression::DataField { name, .. } if name.as_str() == "n") {
+            *expr = count.clone();
+            return;
+        }
+        for sub in expr.sub_expressions_mut() {
+            rec(sub, count);
+        }
+    }
+    let mut ret = rule.clone();
+    rec(&mut ret, count);
+    ret
+}
+
+/// Resolve the plural messages of each locale like the normal ones,
+/// parsing the rule of each catalog.
+///
+/// Every message keeps the rule of the locale which it is taken from.
+pub(super) fn resolve_plurals(
+    path: &str,
+    plurals: &HashMap<String, PluralMessages>,
+    fallbacks: &LocaleFallbacks,
+) -> Result<HashMap<String, HashMap<String, PluralMessage>>, Diagnostic> {
+    let mut parsed: Vec<(String, bool, HashMap<String, PluralMessage>)> = vec![];
+    for (key, plural_messages) in plurals {
+        if plural_messages.messages.is_empty() {
+            continue;
+        }
+        let (locale, is_global) = match key.strip_prefix("global.") {
+            Some(locale) => (locale, true),
+            None => (key.as_str(), false),
+        };
+        let rule_src = match &plural_messages.rule {
+            Some(rule) => rule.as_str(),
+            None => default_plural_rule(locale),
+        };
+        let rule = parse_expression(rule_src).map_err(|message| {
+            Diagnostic::without_location(
+                DiagnosticKind::TranslationFileSyntaxError,
+                path,
+                format!("Invalid plural rule {:?} of {}: {}", rule_src, key, message),
+            )
+        })?;
+        let messages = plural_messages
+            .messages
+            .iter()
+            .map(|(msg_id, forms)| {
+                let message = PluralMessage {
+                    rule: rule.clone(),
+                    forms: forms.clone(),
+                };
+                (msg_id.clone(), message)
+            })
+            .collect();
+        parsed.push((locale.to_string(), is_global, messages));
+    }
+    // the normal locales take precedence over the global ones
+    parsed.sort_by_key(|x| x.1);
+    let mut map: HashMap<String, HashMap<String, PluralMessage>> = HashMap::new();
+    for (locale, _, messages) in parsed {
+        let locale_map = map.entry(locale).or_default();
+        for (msg_id, message) in messages {
+            locale_map.entry(msg_id).or_insert(message);
+        }
+    }
+    apply_fallbacks(&mut map, fallbacks);
+    Ok(map)
+}
+
+/// Select the translated form by the plural rule over the count,
+/// i.e. `rule == 0 ? forms[0] : rule == 1 ? forms[1] : ... : forms[last]`.
+pub(super) fn select_plural_form(
+    rule: &Expression,
+    count: &Expression,
+    forms: Vec<Expression>,
+    location: &Range<Position>,
+) -> Box<Expression> {
+    let index = substitute_count(rule, count);
+    let mut forms = forms.into_iter().enumerate().rev();
+    let (_, last) = forms
+        .next()
+        .expect("plural messages have at least one form");
+    forms.fold(Box::new(last), |acc, (i, form)| {
+        Box::new(Expression::Cond {
+            cond: Box::new(Expression::Eq {
+                left: Box::new(index.clone()),
+                right: Box::new(Expression::LitInt {
+                    value: i as i64,
+                    location: location.clone(),
+                }),
+                location: location.clone(),
+            }),
+            true_br: Box::new(form),
+            false_br: acc,
+            question_location: location.clone(),
+            colon_location: location.clone(),
+        })
+    })
+}
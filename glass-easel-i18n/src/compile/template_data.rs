@@ -0,0 +1,121 @@
+use crate::{Diagnostic, DiagnosticKind};
+use glass_easel_template_compiler::parse::{
+    expr::{Expression, ObjectFieldKind},
+    tag::{ElementKind, Node, Value},
+    TemplateStructure,
+};
+
+/// The data fields read by the locale expression, which sub-templates need in their data.
+pub(super) fn locale_data_fields(locale: &Expression) -> Vec<String> {
+    fn rec(expr: &Expression, ret: &mut Vec<String>) {
+        if let Expression::DataField { name, .. } = expr {
+            if !ret.iter().any(|x| x == name.as_str()) {
+                ret.push(name.to_string());
+            }
+        }
+        for sub in expr.sub_expressions() {
+            rec(sub, ret);
+        }
+    }
+    let mut ret = vec![];
+    rec(locale, &mut ret);
+    ret
+}
+
+/// Pass the `fields` to every `<template is>` call site in the node list.
+///
+/// The fields are prepended to the `data` object, so explicit fields and spreads still take precedence.
+/// If `inject` is false, or the `data` is not an object, the call sites missing any of the fields are reported instead.
+pub(super) fn forward_locale_data(
+    path: &str,
+    node_list: &mut [Node],
+    fields: &[String],
+    inject: bool,
+    warnings: &mut Vec<Diagnostic>,
+) {
+    fn forward(data: &mut Value, fields: &[String], inject: bool) -> Vec<String> {
+        let location = data.location();
+        let missing_in = |obj_fields: &[ObjectFieldKind]| -> Vec<String> {
+            fields
+                .iter()
+                .filter(|field| {
+                    !obj_fields.iter().any(|x| {
+                        matches!(x, ObjectFieldKind::Named { name, .. } if name.as_str() == field.as_str())
+                    })
+                })
+                .cloned()
+                .collect()
+        };
+        let missing = match data {
+            Value::Static { value, .. } if value.is_empty() => fields.to_vec(),
+            Value::Dynamic { expression, .. } => match expression.as_ref() {
+                Expression::LitObj { fields, .. } => missing_in(fields),
+                _ => return fields.to_vec(),
+            },
+            _ => return fields.to_vec(),
+        };
+        if !inject || missing.is_empty() {
+            return missing;
+        }
+        let new_fields = missing.into_iter().map(|name| ObjectFieldKind::Named {
+            name: name.as_str().into(),
+            location: location.start..location.start,
+            colon_location: None,
+            value: Expression::DataField {
+                name: name.as_str().into(),
+                location: location.start..location.start,
+            },
+        });
+        match data {
+            Value::Dynamic { expression, .. } => {
+                if let Expression::LitObj { fields, .. } = expression.as_mut() {
+                    fields.splice(0..0, new_fields);
+                }
+            }
+            _ => {
+                let pos = location.start;
+                let expression = Box::new(Expression::LitObj {
+                    fields: new_fields.collect(),
+                    brace_location: (pos..pos, pos..pos),
+                });
+                *data = Value::new_expression(expression, (pos..pos, pos..pos));
+            }
+        }
+        vec![]
+    }
+    fn rec(
+        path: &str,
+        node: &mut Node,
+        fields: &[String],
+        inject: bool,
+        warnings: &mut Vec<Diagnostic>,
+    ) {
+        let Node::Element(element) = node else {
+            return;
+        };
+        if let ElementKind::TemplateRef { data, .. } = &mut element.kind {
+            let location = element.tag_location.start.0.start..element.tag_location.start.1.end;
+            let missing = forward(&mut data.1, fields, inject);
+            if !missing.is_empty() {
+                warnings.push(Diagnostic::new(
+                    DiagnosticKind::LocaleNotForwarded,
+                    path,
+                    location,
+                    format!(
+                        "The data of the template does not contain {}",
+                        missing.join(", ")
+                    ),
+                ));
+            }
+        }
+        for child in element.iter_children_mut() {
+            rec(path, child, fields, inject, warnings);
+        }
+    }
+    if fields.is_empty() {
+        return;
+    }
+    for node in node_list {
+        rec(path, node, fields, inject, warnings);
+    }
+}
@@ -0,0 +1,339 @@
+use super::{icu::icu_expression, plural::select_plural_form, LocaleMessages};
+use crate::{
+    has_i18n_translate_children, is_i18n_tag,
+    placeholder::{placeholder_name, CONTEXT_ATTR, IGNORE_ATTR},
+    visit_units, ExtractOptions, Placeholder, TranslatableUnit, UnitMessage, UnitTarget,
+};
+use glass_easel_template_compiler::parse::{
+    expr::Expression,
+    tag::{Comment, ElementKind, Node, Value},
+    Position, TemplateStructure,
+};
+use regex::Regex;
+use std::ops::Range;
+
+pub(super) fn remove_i18n_tag(node_list: &[Node]) -> Vec<Node> {
+    let mut new_list = node_list.to_vec();
+    if let Some(pos) = new_list
+        .iter()
+        .position(|node| matches!(node, Node::UnknownMetaTag(tag, ..) if is_i18n_tag(tag)))
+    {
+        new_list.remove(pos);
+    }
+    new_list
+}
+
+/// Remove the `<!I18N ...>` tags left in the nodes, such as `translate-children` and annotations,
+/// and the `i18n-context` and `i18n-ignore` attributes of the elements.
+pub(super) fn remove_i18n_meta_tags(node_list: &mut Vec<Node>) {
+    fn rec(node: &mut Node) {
+        let should_remove = matches!(node, Node::UnknownMetaTag(tag, ..) if is_i18n_tag(tag));
+        if should_remove {
+            *node = Node::Comment(Comment::new("", node.location()));
+        }
+        if let Node::Element(element) = node {
+            if let ElementKind::Normal { attributes, .. } = &mut element.kind {
+                attributes.retain(|x| x.name.name != CONTEXT_ATTR && x.name.name != IGNORE_ATTR);
+            }
+            for child in element.iter_children_mut() {
+                rec(child);
+            }
+        }
+    }
+    for node in node_list {
+        rec(node);
+    }
+}
+
+pub(super) fn split_translated_str(translated_str: String) -> Vec<String> {
+    let mut translated_str_vec: Vec<String> = Vec::new();
+    let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
+    let mut last = 0;
+    for mat in regex.find_iter(&translated_str) {
+        if mat.start() != last {
+            translated_str_vec.push(translated_str[last..mat.start()].to_string());
+        }
+        translated_str_vec.push(translated_str[mat.start()..mat.end()].to_string());
+        last = mat.end();
+    }
+    if last < translated_str.len() {
+        translated_str_vec.push(translated_str[last..].to_string());
+    }
+    translated_str_vec
+}
+
+/// Replace the `{{x}}` parts of a text, which can only be written at the top level of a value,
+/// with `(x===undefined?"":x)` so that the expression can be nested.
+pub(super) fn inline_to_string(expr: &mut Expression) {
+    if let Expression::ToStringWithoutUndefined { value, location } = expr {
+        let location = location.clone();
+        let mut value = std::mem::replace(
+            value,
+            Box::new(Expression::LitUndefined {
+                location: location.clone(),
+            }),
+        );
+        inline_to_string(&mut value);
+        *expr = Expression::Cond {
+            cond: Box::new(Expression::EqFull {
+                left: value.clone(),
+                right: Box::new(Expression::LitUndefined {
+                    location: location.clone(),
+                }),
+                location: location.clone(),
+            }),
+            true_br: Box::new(Expression::LitStr {
+                value: "".into(),
+                location: location.clone(),
+            }),
+            false_br: value,
+            question_location: location.clone(),
+            colon_location: location,
+        };
+        return;
+    }
+    for sub in expr.sub_expressions_mut() {
+        inline_to_string(sub);
+    }
+}
+
+/// The string expression of a translation, filling the placeholders with the expressions in `args`.
+fn translated_expression(
+    translation: &str,
+    args: &[(String, Box<Expression>)],
+    location: &Range<Position>,
+) -> Box<Expression> {
+    let get_expr = |item: &str| -> Box<Expression> {
+        let arg = placeholder_name(item).and_then(|name| args.iter().find(|x| x.0 == name));
+        match arg {
+            Some((_, value)) => Box::new(Expression::ToStringWithoutUndefined {
+                value: value.clone(),
+                location: location.clone(),
+            }),
+            None => Box::new(Expression::LitStr {
+                value: item.into(),
+                location: location.clone(),
+            }),
+        }
+    };
+    split_translated_str(translation.to_string())
+        .into_iter()
+        .map(|item| get_expr(&item))
+        .fold(None, |acc, x| match acc {
+            None => Some(x),
+            Some(acc) => Some(Box::new(Expression::Plus {
+                left: acc,
+                right: x,
+                location: location.clone(),
+            })),
+        })
+        // an empty translation has no parts at all
+        .unwrap_or_else(|| {
+            Box::new(Expression::LitStr {
+                value: "".into(),
+                location: location.clone(),
+            })
+        })
+}
+
+/// Add the outer whitespace of the original text around a string expression.
+///
+/// The expression must not contain `{{x}}` parts, see [`inline_to_string`].
+pub(super) fn with_outer_whitespace(
+    expression: Box<Expression>,
+    message: &UnitMessage,
+    location: &Range<Position>,
+) -> Box<Expression> {
+    let mut ret = expression;
+    if !message.leading.is_empty() {
+        ret = Box::new(Expression::Plus {
+            left: Box::new(Expression::LitStr {
+                value: message.leading.as_str().into(),
+                location: location.clone(),
+            }),
+            right: ret,
+            location: location.clone(),
+        });
+    }
+    if !message.trailing.is_empty() {
+        ret = Box::new(Expression::Plus {
+            left: ret,
+            right: Box::new(Expression::LitStr {
+                value: message.trailing.as_str().into(),
+                location: location.clone(),
+            }),
+            location: location.clone(),
+        });
+    }
+    ret
+}
+
+/// Translate a text node or an attribute value, returning whether a translation is found.
+///
+/// The translation keeps the outer whitespace of the source.
+/// If the message has plural forms and its count placeholder is annotated,
+/// the form is selected by the plural rule over the bound value of the placeholder.
+/// ICU messages with `plural` or `select` arguments are selected by the bound values likewise.
+pub(super) fn translate_value(
+    value: &mut Value,
+    message: &UnitMessage,
+    trans_content_map: &LocaleMessages,
+) -> bool {
+    let key = message.key();
+    match value {
+        Value::Static { ref mut value, .. } => {
+            if let Some(translation) = trans_content_map.get(&key) {
+                *value = message.restore(translation).into();
+                return true;
+            }
+        }
+        Value::Dynamic {
+            ref mut expression,
+            double_brace_location,
+            ..
+        } => {
+            let args = message.expressions();
+            let location = &double_brace_location.0;
+            if let (Some(count), Some(plural)) =
+                (message.plural_count(), trans_content_map.plurals.get(&key))
+            {
+                let forms = plural
+                    .forms
+                    .iter()
+                    .map(|form| {
+                        let mut form =
+                            translated_expression(&message.restore(form), &args, location);
+                        inline_to_string(&mut form);
+                        *form
+                    })
+                    .collect();
+                *expression = select_plural_form(&plural.rule, count, forms, location);
+                return true;
+            }
+            if let Some(icu_message) = trans_content_map.icu_messages.get(&key) {
+                let Some(translated) = icu_expression(icu_message, &args, location) else {
+                    return false;
+                };
+                *expression = with_outer_whitespace(translated, message, location);
+                return true;
+            }
+            if let Some(translation) = trans_content_map.get(&key) {
+                *expression = translated_expression(&message.restore(translation), &args, location);
+                return true;
+            }
+        }
+        _ => {}
+    }
+    false
+}
+
+/// Translate the children of an element with `<!I18N translate-children>`, returning whether a translation is found.
+///
+/// The meta tag is always removed.
+pub(super) fn translate_entire_children(
+    node_list: &mut Vec<Node>,
+    message: &UnitMessage,
+    trans_content_map: &LocaleMessages,
+) -> bool {
+    node_list.retain(
+        |node| !matches!(node, Node::UnknownMetaTag(tag, ..) if has_i18n_translate_children(tag)),
+    );
+    let key = message.key();
+    // the ICU messages with plural or select arguments cannot select the nodes, which is reported when validating
+    if trans_content_map.icu_messages.contains_key(&key) {
+        return false;
+    }
+    let Some(translation) = trans_content_map.get(&key) else {
+        return false;
+    };
+    let Some(first_node) = node_list.first() else {
+        return false;
+    };
+    let first_text_node = node_list
+        .iter()
+        .find(|node| matches!(node, Node::Text(Value::Static { .. })))
+        .cloned()
+        .unwrap_or_else(|| Node::Text(Value::new_empty(first_node.location().start)));
+    let mut new_node_list: Vec<Node> = Vec::new();
+    for item in split_translated_str(message.restore(translation)) {
+        let placeholder = placeholder_name(&item).and_then(|name| {
+            message
+                .placeholders
+                .iter()
+                .find_map(|(x, placeholder)| match placeholder {
+                    Placeholder::Node(node) if x == name => Some(&**node),
+                    _ => None,
+                })
+        });
+        if let Some(placeholder) = placeholder {
+            new_node_list.push(placeholder.clone());
+        } else {
+            let mut static_text = first_text_node.clone();
+            let Node::Text(Value::Static { ref mut value, .. }) = static_text else {
+                unreachable!()
+            };
+            *value = item.into();
+            new_node_list.push(static_text);
+        }
+    }
+    *node_list = new_node_list;
+    true
+}
+
+/// Translate a string literal inside a data binding, returning whether a translation is found.
+pub(super) fn translate_literal(
+    expression: &mut Expression,
+    message: &UnitMessage,
+    trans_content_map: &LocaleMessages,
+) -> bool {
+    let Expression::LitStr { value, .. } = expression else {
+        return false;
+    };
+    let Some(translation) = trans_content_map.get(&message.key()) else {
+        return false;
+    };
+    *value = message.restore(translation).into();
+    true
+}
+
+/// Translate a unit with the translations of a single locale, returning whether to visit the units inside it.
+pub(super) fn translate_unit(unit: TranslatableUnit, trans_content_map: &LocaleMessages) -> bool {
+    match unit.target {
+        UnitTarget::Value(value) => {
+            translate_value(value, &unit.message, trans_content_map);
+        }
+        UnitTarget::Children(children) => {
+            translate_entire_children(children, &unit.message, trans_content_map);
+        }
+        UnitTarget::Literal(expression) => {
+            translate_literal(expression, &unit.message, trans_content_map);
+        }
+    }
+    true
+}
+
+/// Translate the nodes with the translations of a single locale.
+pub(super) fn translate(
+    node_list: &mut [Node],
+    trans_content_map: &LocaleMessages,
+    options: ExtractOptions,
+) {
+    visit_units(node_list, options, None, &mut |unit| {
+        translate_unit(unit, trans_content_map)
+    });
+}
+
+/// Replace the value with a data binding of the expression.
+pub(super) fn set_value_expression(value: &mut Value, expression: Box<Expression>) {
+    let double_brace_location = match value {
+        Value::Dynamic {
+            double_brace_location,
+            ..
+        } => double_brace_location.clone(),
+        _ => {
+            let location = value.location();
+            (location.start..location.start, location.end..location.end)
+        }
+    };
+    *value = Value::new_expression(expression, double_brace_location);
+}
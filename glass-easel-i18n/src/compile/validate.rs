@@ -0,0 +1,226 @@
+use super::{
+    fallback::FallbackSources, icu::message_arguments, OrderedTransContent, VisitedMessage,
+};
+use crate::{Diagnostic, DiagnosticKind, Placeholder, UnitMessage};
+use glass_easel_template_compiler::parse::tag::Node;
+use regex::Regex;
+
+/// How the translations with mismatched placeholders are reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaceholderCheck {
+    /// Add the mismatches to the warnings of the output.
+    #[default]
+    Warning,
+    /// Fail the compilation with the first mismatch.
+    Error,
+}
+
+impl std::str::FromStr for PlaceholderCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("Unknown placeholder check level: {}", s)),
+        }
+    }
+}
+
+/// The placeholder names in a message with their counts, and whether there are unpaired braces.
+fn message_placeholders(regex: &Regex, message: &str) -> (Vec<(String, usize)>, bool) {
+    let mut ret: Vec<(String, usize)> = vec![];
+    let mut malformed = false;
+    for m in regex.find_iter(message) {
+        let name = m.as_str()[2..m.len() - 2].trim();
+        if name.is_empty() || name.contains('{') {
+            malformed = true;
+            continue;
+        }
+        match ret.iter_mut().find(|x| x.0 == name) {
+            Some(x) => x.1 += 1,
+            None => ret.push((name.to_string(), 1)),
+        }
+    }
+    let rest = regex.replace_all(message, "");
+    if rest.contains("{{") || rest.contains("}}") {
+        malformed = true;
+    }
+    (ret, malformed)
+}
+
+/// Find the problems of the placeholders in a translation of the msgid.
+///
+/// The placeholders in `optional` are allowed to be missing.
+fn placeholder_problems(
+    regex: &Regex,
+    msgid: &str,
+    translation: &str,
+    optional: &[&str],
+) -> Vec<String> {
+    let (expected, _) = message_placeholders(regex, msgid);
+    let (found, malformed) = message_placeholders(regex, translation);
+    let mut ret = vec![];
+    for (name, count) in &expected {
+        match found.iter().find(|x| x.0 == *name) {
+            None if optional.contains(&name.as_str()) => {}
+            None => ret.push(format!("missing placeholder {{{{{}}}}}", name)),
+            Some((_, found_count)) if found_count > count => {
+                ret.push(format!("duplicated placeholder {{{{{}}}}}", name))
+            }
+            _ => {}
+        }
+    }
+    for (name, _) in &found {
+        if !expected.iter().any(|x| x.0 == *name) {
+            ret.push(format!("unknown placeholder {{{{{}}}}}", name));
+        }
+    }
+    if malformed {
+        ret.push("malformed placeholder".to_string());
+    }
+    ret
+}
+
+/// The names of all placeholders of the message.
+fn all_placeholders(message: &UnitMessage) -> Vec<&str> {
+    message
+        .placeholders
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect()
+}
+
+/// The names of the placeholders standing for the elements of a translate-children group.
+///
+/// The data bindings in the group are not included, so they are still required in translations.
+fn element_placeholders(message: &UnitMessage) -> Vec<&str> {
+    message
+        .placeholders
+        .iter()
+        .filter_map(|(name, x)| match x {
+            Placeholder::Node(node) if matches!(**node, Node::Element(_)) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Find the problems of the arguments in an ICU message of the msgid.
+///
+/// The arguments can be repeated in the cases of `plural` and `select` arguments.
+fn argument_problems(regex: &Regex, msgid: &str, arguments: &[String]) -> Vec<String> {
+    let (expected, _) = message_placeholders(regex, msgid);
+    let mut ret = vec![];
+    for (name, _) in &expected {
+        if !arguments.contains(name) {
+            ret.push(format!("missing placeholder {{{{{}}}}}", name));
+        }
+    }
+    for name in arguments {
+        if !expected.iter().any(|x| x.0 == *name) {
+            ret.push(format!("unknown placeholder {{{{{}}}}}", name));
+        }
+    }
+    ret
+}
+
+/// Check the translations of the messages in the template against the placeholders of their msgids.
+///
+/// The translations taken from fallback locales are only checked in their own locales.
+/// Each form of a plural message is checked separately.
+/// The translations which cannot be parsed in the message format are reported as well.
+pub(super) fn validate_placeholders(
+    path: &str,
+    messages: &[VisitedMessage],
+    trans_content: &OrderedTransContent,
+    fallback_sources: &FallbackSources,
+) -> Vec<Diagnostic> {
+    let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
+    let mut ret = vec![];
+    for VisitedMessage {
+        message,
+        location,
+        is_group,
+    } in messages
+    {
+        let key = message.key();
+        for (locale, trans_content_map) in &trans_content.map {
+            let is_fallback = fallback_sources
+                .get(locale)
+                .is_some_and(|x| x.contains_key(&key));
+            if is_fallback {
+                continue;
+            }
+            if let Some(err) = trans_content_map.invalid.get(&key) {
+                ret.push(Diagnostic::new(
+                    DiagnosticKind::InvalidMessage,
+                    path,
+                    location.clone(),
+                    format!(
+                        "The translation of {:?} in {} is not a valid ICU message: {}",
+                        message.msgid, locale, err,
+                    ),
+                ));
+                continue;
+            }
+            if *is_group && trans_content_map.icu_messages.contains_key(&key) {
+                ret.push(Diagnostic::new(
+                    DiagnosticKind::InvalidMessage,
+                    path,
+                    location.clone(),
+                    format!(
+                        "The translation of {:?} in {} has plural or select arguments, which are not supported in translate-children groups",
+                        message.msgid, locale,
+                    ),
+                ));
+                continue;
+            }
+            if let Some(icu_message) = trans_content_map.icu_messages.get(&key) {
+                let arguments = message_arguments(&icu_message.parts);
+                let problems = argument_problems(&regex, &message.msgid, &arguments);
+                if !problems.is_empty() {
+                    ret.push(Diagnostic::new(
+                        DiagnosticKind::PlaceholderMismatch,
+                        path,
+                        location.clone(),
+                        format!(
+                            "The translation of {:?} in {} has {}",
+                            message.msgid,
+                            locale,
+                            problems.join(", "),
+                        ),
+                    ));
+                }
+                continue;
+            }
+            let Some(translation) = trans_content_map.get(&key) else {
+                continue;
+            };
+            // a plural form can leave out the count, e.g. "one file",
+            // and a translate-children group can be translated without its elements
+            let (translations, optional) = match trans_content_map.plurals.get(&key) {
+                Some(plural) => (plural.forms.iter().collect(), all_placeholders(message)),
+                None => (vec![translation], element_placeholders(message)),
+            };
+            for translation in translations {
+                let problems = placeholder_problems(&regex, &message.msgid, translation, &optional);
+                if problems.is_empty() {
+                    continue;
+                }
+                ret.push(Diagnostic::new(
+                    DiagnosticKind::PlaceholderMismatch,
+                    path,
+                    location.clone(),
+                    format!(
+                        "The translation of {:?} in {} has {}: {:?}",
+                        message.msgid,
+                        locale,
+                        problems.join(", "),
+                        translation,
+                    ),
+                ));
+            }
+        }
+    }
+    ret
+}
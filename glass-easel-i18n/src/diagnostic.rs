@@ -0,0 +1,135 @@
+use std::ops::Range;
+
+use glass_easel_template_compiler::parse::{ParseError, ParseErrorLevel, Position};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The template cannot be parsed.
+    TemplateParseError,
+    /// The template can be parsed but is likely to contain mistakes.
+    TemplateParseWarning,
+    /// The translation file (TOML or PO) cannot be parsed.
+    TranslationFileSyntaxError,
+    /// The placeholders in a translation do not match the ones in its msgid.
+    PlaceholderMismatch,
+    /// A translation cannot be parsed in the message format.
+    InvalidMessage,
+    /// The output cannot be generated.
+    OutputError,
+    /// An option passed to the compiler is invalid.
+    InvalidOption,
+    /// A `<template is>` call site does not pass the locale to the sub-template.
+    LocaleNotForwarded,
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Self::TemplateParseError => "template parse error",
+            Self::TemplateParseWarning => "template parse warning",
+            Self::TranslationFileSyntaxError => "translation file syntax error",
+            Self::PlaceholderMismatch => "placeholder mismatch",
+            Self::InvalidMessage => "invalid message",
+            Self::OutputError => "output error",
+            Self::InvalidOption => "invalid option",
+            Self::LocaleNotForwarded => "locale not forwarded",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// An error or a warning with its location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub path: String,
+    pub location: Range<Position>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}:{}:{}-{}:{}: {}",
+            self.kind,
+            self.path,
+            self.location.start.line + 1,
+            self.location.start.utf16_col + 1,
+            self.location.end.line + 1,
+            self.location.end.utf16_col + 1,
+            self.message,
+        )
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl Diagnostic {
+    pub fn new(
+        kind: DiagnosticKind,
+        path: &str,
+        location: Range<Position>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            path: path.to_string(),
+            location,
+            message: message.into(),
+        }
+    }
+
+    /// Create a diagnostic without a meaningful location.
+    pub fn without_location(kind: DiagnosticKind, path: &str, message: impl Into<String>) -> Self {
+        Self::new(
+            kind,
+            path,
+            Position::default()..Position::default(),
+            message,
+        )
+    }
+
+    fn from_parse_error(err: &ParseError) -> Self {
+        let kind = if err.prevent_success() {
+            DiagnosticKind::TemplateParseError
+        } else {
+            DiagnosticKind::TemplateParseWarning
+        };
+        Self::new(kind, &err.path, err.location.clone(), err.kind.to_string())
+    }
+}
+
+/// Convert the template parsing warnings into diagnostics.
+///
+/// Returns the first error if any of them prevents a successful compilation.
+/// Notes are dropped since `<!I18N>` itself always produces one.
+pub(crate) fn collect_parse_warnings<'a>(
+    warnings: impl Iterator<Item = &'a ParseError>,
+) -> Result<Vec<Diagnostic>, Diagnostic> {
+    let mut ret = vec![];
+    for warning in warnings {
+        if warning.prevent_success() {
+            return Err(Diagnostic::from_parse_error(warning));
+        }
+        if warning.level() >= ParseErrorLevel::Warn {
+            ret.push(Diagnostic::from_parse_error(warning));
+        }
+    }
+    Ok(ret)
+}
+
+/// Get the position of a byte offset in the source.
+pub(crate) fn position_of_offset(source: &str, offset: usize) -> Position {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line = before.matches('\n').count() as u32;
+    let line_start = before.rfind('\n').map(|x| x + 1).unwrap_or(0);
+    Position {
+        line,
+        utf16_col: before[line_start..].encode_utf16().count() as u32,
+    }
+}
@@ -0,0 +1,551 @@
+use crate::{
+    contains_i18n_translate_children, is_attribute_included, msg_key,
+    placeholder::{
+        dynamic_message, element_context, is_ignored, note_attr, split_expression,
+        translator_notes, PlaceholderNames,
+    },
+    AttributeRule, LiteralPolicy, PlaceholderStyle, WhitespacePolicy,
+};
+use glass_easel_template_compiler::parse::{
+    expr::Expression,
+    tag::{ElementKind, Node, Value},
+    Position, TemplateStructure,
+};
+use std::ops::Range;
+
+/// Where a translatable unit comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TermOrigin {
+    /// A text node.
+    Text,
+    /// The value of the attribute with the name.
+    Attribute(String),
+    /// The children of an element with `<!I18N translate-children>`.
+    TranslateChildren,
+}
+
+/// Options of finding the translatable units, shared by searching and compiling.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct ExtractOptions<'a> {
+    /// The rules selecting the attributes to translate besides the text nodes.
+    pub attributes: &'a [AttributeRule],
+    /// How the placeholders in msgids are named.
+    pub placeholder_style: PlaceholderStyle,
+    /// How the whitespace of texts is normalized into msgids.
+    pub whitespace: WhitespacePolicy,
+    /// Which string literals inside data bindings are translated.
+    pub literals: LiteralPolicy,
+    /// Only visit the string literals of the values and the groups without messages,
+    /// leaving the others to [`visit_value_literals`] and [`visit_message_literals`].
+    pub(crate) literals_with_messages: bool,
+}
+
+impl<'a> ExtractOptions<'a> {
+    /// Create the options with the attribute rules, in which the other options are the defaults.
+    pub fn new(attributes: &'a [AttributeRule]) -> Self {
+        Self {
+            attributes,
+            ..Default::default()
+        }
+    }
+}
+
+/// What a placeholder in a msgid stands for.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Placeholder {
+    /// A data binding, whose value fills the placeholder.
+    Expression(Box<Expression>),
+    /// A node in a translate-children group, or a text node of a data binding in it.
+    Node(Box<Node>),
+}
+
+/// The message of a translatable unit.
+#[derive(Debug, Clone)]
+pub struct UnitMessage {
+    /// The msgid, normalized by the whitespace policy.
+    pub msgid: String,
+    /// The message context (msgctxt) given by `<!I18N context="...">` or an `i18n-context` attribute.
+    pub context: Option<String>,
+    /// The placeholders in the msgid in order, each one only once.
+    pub placeholders: Vec<(String, Placeholder)>,
+    /// The placeholder which selects the plural form, given by `<!I18N plural="...">`.
+    pub plural: Option<String>,
+    /// The notes to translators given by `<!-- i18n: ... -->` comments or `<!I18N note="...">`.
+    pub notes: Vec<String>,
+    /// The whitespace before the msgid in the source, kept around the translation.
+    pub leading: String,
+    /// The whitespace after the msgid in the source, kept around the translation.
+    pub trailing: String,
+}
+
+impl UnitMessage {
+    /// The key of the message in translation maps, which contains the message context if any.
+    pub fn key(&self) -> String {
+        msg_key(self.context.as_deref(), &self.msgid)
+    }
+
+    /// The translation with the outer whitespace of the source.
+    pub fn restore(&self, translation: &str) -> String {
+        format!("{}{}{}", self.leading, translation, self.trailing)
+    }
+
+    /// The data bindings of the placeholders.
+    pub fn expressions(&self) -> Vec<(String, Box<Expression>)> {
+        self.placeholders
+            .iter()
+            .filter_map(|(name, x)| match x {
+                Placeholder::Expression(expr) => Some((name.clone(), expr.clone())),
+                Placeholder::Node(_) => None,
+            })
+            .collect()
+    }
+
+    /// The bound value of the plural placeholder.
+    pub fn plural_count(&self) -> Option<&Expression> {
+        let plural = self.plural.as_ref()?;
+        self.placeholders.iter().find_map(|(name, x)| match x {
+            Placeholder::Expression(expr) if name == plural => Some(&**expr),
+            _ => None,
+        })
+    }
+}
+
+/// The part of the template which a translatable unit rewrites.
+#[non_exhaustive]
+pub enum UnitTarget<'a> {
+    /// A text node or an attribute value.
+    Value(&'a mut Value),
+    /// The children of an element with `<!I18N translate-children>`, including the meta tag.
+    Children(&'a mut Vec<Node>),
+    /// A string literal inside a data binding, see [`LiteralPolicy`].
+    Literal(&'a mut Expression),
+}
+
+/// A message found in a template, with the part of the template to rewrite with its translation.
+pub struct TranslatableUnit<'a> {
+    pub message: UnitMessage,
+    pub origin: TermOrigin,
+    pub location: Range<Position>,
+    /// The tag name of the closest enclosing element.
+    pub tag_name: Option<String>,
+    /// The message context inherited from the elements, which applies to the units inside a translate-children group.
+    pub inherited_context: Option<String>,
+    pub target: UnitTarget<'a>,
+}
+
+/// Find the translatable units in the nodes in source order, calling `visitor` with each one.
+///
+/// `context` is the message context inherited from the enclosing elements, if any.
+/// The nodes excluded by `<!I18N ignore>` or `i18n-ignore` are skipped.
+/// A translate-children group is visited before the elements inside,
+/// which are only visited if `visitor` returns `true` for the group,
+/// so that the group can be rewritten by the visitor first.
+/// The string literals inside a value are visited before the value itself,
+/// so that the rewritten literals are kept in the placeholders of the value.
+/// The return values for other units are ignored.
+pub fn visit_units<F>(
+    node_list: &mut [Node],
+    options: ExtractOptions,
+    context: Option<&str>,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    for index in 0..node_list.len() {
+        visit_node(node_list, index, options, context, None, visitor);
+    }
+}
+
+/// Find the translatable units inside a translate-children group, i.e. the ones of its elements.
+///
+/// The texts of the group are a part of the message of the group itself.
+pub fn visit_group<F>(
+    node_list: &mut [Node],
+    options: ExtractOptions,
+    context: Option<&str>,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    visit_group_nodes(node_list, options, context, None, visitor);
+}
+
+fn visit_group_nodes<F>(
+    node_list: &mut [Node],
+    options: ExtractOptions,
+    context: Option<&str>,
+    tag_name: Option<&str>,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    for index in 0..node_list.len() {
+        if matches!(node_list[index], Node::Element(_)) {
+            visit_node(node_list, index, options, context, tag_name, visitor);
+        }
+    }
+}
+
+fn visit_node<F>(
+    node_list: &mut [Node],
+    index: usize,
+    options: ExtractOptions,
+    context: Option<&str>,
+    tag_name: Option<&str>,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    if is_ignored(node_list, index) {
+        return;
+    }
+    let element_context = element_context(&node_list[index]);
+    let context = element_context.as_deref().or(context);
+    let names = PlaceholderNames::annotated(options.placeholder_style, context, node_list, index);
+    let notes = translator_notes(node_list, index);
+    match &mut node_list[index] {
+        Node::Element(element) => match &mut element.kind {
+            ElementKind::Normal {
+                tag_name,
+                attributes,
+                children,
+                ..
+            } => {
+                let element_tag_name = tag_name.name.as_str();
+                let tag_name = Some(element_tag_name);
+                for attribute in attributes.iter_mut() {
+                    let name = attribute.name.name.to_string();
+                    if !is_attribute_included(options.attributes, element_tag_name, &name) {
+                        continue;
+                    }
+                    if let Some(value) = &mut attribute.value {
+                        let origin = TermOrigin::Attribute(name);
+                        let context = names.context();
+                        if !options.literals_with_messages
+                            || !has_value_message(value, names.clone(), options)
+                        {
+                            visit_literals(
+                                value, &origin, context, &notes, tag_name, options, visitor,
+                            );
+                        }
+                        let unit = value_unit(value, origin, names.clone(), &notes, options);
+                        if let Some(unit) = unit {
+                            visitor(TranslatableUnit {
+                                tag_name: tag_name.map(|x| x.to_string()),
+                                ..unit
+                            });
+                        }
+                    }
+                }
+                if contains_i18n_translate_children(children) {
+                    let skip_literals = options.literals_with_messages
+                        && group_message(children, context, &notes, options).is_some()
+                        && group_location(children).is_some();
+                    for node in children.iter_mut().filter(|_| !skip_literals) {
+                        if let Node::Text(value) = node {
+                            let origin = TermOrigin::TranslateChildren;
+                            visit_literals(
+                                value, &origin, context, &notes, tag_name, options, visitor,
+                            );
+                        }
+                    }
+                    let message = group_message(children, context, &notes, options);
+                    let location = group_location(children);
+                    let descend = match (message, location) {
+                        (Some(message), Some(location)) => visitor(TranslatableUnit {
+                            message,
+                            origin: TermOrigin::TranslateChildren,
+                            location,
+                            tag_name: tag_name.map(|x| x.to_string()),
+                            inherited_context: context.map(|x| x.to_string()),
+                            target: UnitTarget::Children(&mut *children),
+                        }),
+                        _ => true,
+                    };
+                    if descend {
+                        visit_group_nodes(children, options, context, tag_name, visitor);
+                    }
+                } else {
+                    for index in 0..children.len() {
+                        visit_node(children, index, options, context, tag_name, visitor);
+                    }
+                }
+            }
+            ElementKind::If {
+                branches,
+                else_branch,
+                ..
+            } => {
+                let node_lists = branches
+                    .iter_mut()
+                    .map(|x| &mut x.2)
+                    .chain(else_branch.iter_mut().map(|x| &mut x.1));
+                for node_list in node_lists {
+                    for index in 0..node_list.len() {
+                        visit_node(node_list, index, options, context, tag_name, visitor);
+                    }
+                }
+            }
+            ElementKind::For { children, .. } | ElementKind::Pure { children, .. } => {
+                for index in 0..children.len() {
+                    visit_node(children, index, options, context, tag_name, visitor);
+                }
+            }
+            _ => {}
+        },
+        Node::Text(value) => {
+            let origin = TermOrigin::Text;
+            let context = names.context();
+            if !options.literals_with_messages || !has_value_message(value, names.clone(), options)
+            {
+                visit_literals(value, &origin, context, &notes, tag_name, options, visitor);
+            }
+            if let Some(unit) = value_unit(value, TermOrigin::Text, names, &notes, options) {
+                visitor(TranslatableUnit {
+                    tag_name: tag_name.map(|x| x.to_string()),
+                    ..unit
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Visit the units of the string literals inside the data bindings of a value.
+fn visit_literals<F>(
+    value: &mut Value,
+    origin: &TermOrigin,
+    context: Option<&str>,
+    notes: &[String],
+    tag_name: Option<&str>,
+    options: ExtractOptions,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    let Value::Dynamic { expression, .. } = value else {
+        return;
+    };
+    visit_expression_literals(
+        expression, origin, context, notes, tag_name, options, visitor,
+    );
+}
+
+fn visit_expression_literals<F>(
+    expression: &mut Expression,
+    origin: &TermOrigin,
+    context: Option<&str>,
+    notes: &[String],
+    tag_name: Option<&str>,
+    options: ExtractOptions,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    for literal in options.literals.literals(expression) {
+        let Expression::LitStr { value, location } = &*literal else {
+            continue;
+        };
+        let text = options.whitespace.normalize(value);
+        let message = UnitMessage {
+            msgid: text.msgid,
+            context: context.map(|x| x.to_string()),
+            placeholders: vec![],
+            plural: None,
+            notes: notes.to_vec(),
+            leading: text.leading,
+            trailing: text.trailing,
+        };
+        visitor(TranslatableUnit {
+            message,
+            origin: origin.clone(),
+            location: location.clone(),
+            tag_name: tag_name.map(|x| x.to_string()),
+            inherited_context: None,
+            target: UnitTarget::Literal(literal),
+        });
+    }
+}
+
+/// Visit the units of the string literals inside a text node or an attribute value,
+/// in the same way as [`visit_units`] does before visiting the value itself.
+///
+/// `context` is the message context of the literals, i.e. the one of the value.
+pub(crate) fn visit_value_literals<F>(
+    value: &mut Value,
+    context: Option<&str>,
+    options: ExtractOptions,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    visit_literals(
+        value,
+        &TermOrigin::Text,
+        context,
+        &[],
+        None,
+        options,
+        visitor,
+    );
+}
+
+/// Visit the units of the string literals inside the placeholders of a message,
+/// which are the ones of the value or the translate-children group of the message.
+///
+/// `context` is the message context of the literals, which is inherited from the elements for a group.
+pub(crate) fn visit_message_literals<F>(
+    message: &mut UnitMessage,
+    context: Option<&str>,
+    options: ExtractOptions,
+    visitor: &mut F,
+) where
+    F: FnMut(TranslatableUnit) -> bool,
+{
+    for (_, placeholder) in message.placeholders.iter_mut() {
+        let expression = match placeholder {
+            Placeholder::Expression(expression) => expression,
+            Placeholder::Node(node) => match &mut **node {
+                Node::Text(Value::Dynamic { expression, .. }) => expression,
+                _ => continue,
+            },
+        };
+        visit_expression_literals(
+            expression,
+            &TermOrigin::Text,
+            context,
+            &[],
+            None,
+            options,
+            visitor,
+        );
+    }
+}
+
+/// Whether a text node or an attribute value has a message, i.e. it is not empty.
+fn has_value_message(value: &Value, names: PlaceholderNames, options: ExtractOptions) -> bool {
+    let text = match value {
+        Value::Static { value, .. } => value.to_string(),
+        Value::Dynamic { expression, .. } => dynamic_message(expression, names).0,
+        _ => return false,
+    };
+    !options.whitespace.normalize(&text).msgid.trim().is_empty()
+}
+
+/// The unit of a text node or an attribute value, unless it is empty.
+fn value_unit<'a>(
+    value: &'a mut Value,
+    origin: TermOrigin,
+    names: PlaceholderNames,
+    notes: &[String],
+    options: ExtractOptions,
+) -> Option<TranslatableUnit<'a>> {
+    let (text, placeholders) = match &*value {
+        Value::Static { value, .. } => (value.to_string(), vec![]),
+        Value::Dynamic { expression, .. } => dynamic_message(expression, names.clone()),
+        _ => return None,
+    };
+    let text = options.whitespace.normalize(&text);
+    if text.msgid.trim().is_empty() {
+        return None;
+    }
+    let plural = names
+        .plural()
+        .filter(|name| placeholders.iter().any(|x| x.0 == *name))
+        .map(|x| x.to_string());
+    let message = UnitMessage {
+        msgid: text.msgid,
+        context: names.context().map(|x| x.to_string()),
+        placeholders: placeholders
+            .into_iter()
+            .map(|(name, expr)| (name, Placeholder::Expression(expr)))
+            .collect(),
+        plural,
+        notes: notes.to_vec(),
+        leading: text.leading,
+        trailing: text.trailing,
+    };
+    Some(TranslatableUnit {
+        message,
+        origin,
+        location: value.location(),
+        tag_name: None,
+        inherited_context: None,
+        target: UnitTarget::Value(value),
+    })
+}
+
+/// The message of a translate-children group, unless it is empty.
+///
+/// The elements and the data bindings become placeholders, while the meta tags and the comments are left out.
+fn group_message(
+    node_list: &[Node],
+    context: Option<&str>,
+    notes: &[String],
+    options: ExtractOptions,
+) -> Option<UnitMessage> {
+    let mut names = PlaceholderNames::group(options.placeholder_style, context, node_list);
+    let mut notes = notes.to_vec();
+    let mut text_vec: Vec<String> = vec![];
+    let mut placeholders = vec![];
+    for node in node_list {
+        match node {
+            Node::UnknownMetaTag(tag) => notes.extend(note_attr(tag)),
+            Node::Text(Value::Static { value, .. }) => text_vec.push(value.to_string()),
+            Node::Text(Value::Dynamic {
+                expression,
+                double_brace_location,
+                ..
+            }) => {
+                // the bindings are filled with text nodes of them
+                let mut expr_vec = vec![];
+                let mut args = vec![];
+                split_expression(expression, &mut expr_vec, &mut names, &mut args);
+                text_vec.push(expr_vec.join(""));
+                for (name, value) in args {
+                    let location = value.location();
+                    let expression =
+                        Box::new(Expression::ToStringWithoutUndefined { value, location });
+                    let value = Value::new_expression(expression, double_brace_location.clone());
+                    placeholders.push((name, Placeholder::Node(Box::new(Node::Text(value)))));
+                }
+            }
+            Node::Element(element) => {
+                let tag_name = match &element.kind {
+                    ElementKind::Normal { tag_name, .. } => Some(tag_name.name.as_str()),
+                    _ => None,
+                };
+                let name = names.node(tag_name);
+                text_vec.push(format!("{{{{{}}}}}", name));
+                placeholders.push((name, Placeholder::Node(Box::new(node.clone()))));
+            }
+            _ => {}
+        }
+    }
+    let text = options.whitespace.normalize_parts(&text_vec);
+    if text.msgid.trim().is_empty() {
+        return None;
+    }
+    Some(UnitMessage {
+        msgid: text.msgid,
+        context: names.context().map(|x| x.to_string()),
+        placeholders,
+        plural: None,
+        notes,
+        leading: text.leading,
+        trailing: text.trailing,
+    })
+}
+
+/// The location of a translate-children group, from its first node to its last node except the meta tags.
+fn group_location(node_list: &[Node]) -> Option<Range<Position>> {
+    let mut nodes = node_list
+        .iter()
+        .filter(|x| !matches!(x, Node::UnknownMetaTag(..)))
+        .map(|x| x.location());
+    let first = nodes.next()?;
+    let end = nodes.next_back().map(|x| x.end).unwrap_or(first.end);
+    Some(first.start..end)
+}
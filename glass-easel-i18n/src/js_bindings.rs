@@ -1,9 +1,13 @@
 use wasm_bindgen::prelude::*;
 
-use crate::{compile, search, CompiledTemplate, UntranslatedTerms};
+use crate::{
+    compile, compile_per_locale, compile_po, compile_with_options, parse_po, search,
+    search_with_options, write_po, CompileOptions, CompiledLocales, CompiledTemplate, Diagnostic,
+    DiagnosticKind, PoCatalog, SearchOptions, TransContent, UntranslatedTerms,
+};
 
 #[wasm_bindgen]
-pub struct JsCompileResult(Result<CompiledTemplate, String>);
+pub struct JsCompileResult(Result<CompiledTemplate, Diagnostic>);
 
 #[wasm_bindgen]
 impl JsCompileResult {
@@ -18,10 +22,7 @@ impl JsCompileResult {
     #[wasm_bindgen(js_name = "getOutput")]
     pub fn output(&self) -> Option<String> {
         match &self.0 {
-            Ok(CompiledTemplate {
-                output,
-                source_map: _,
-            }) => Some(output.clone()),
+            Ok(CompiledTemplate { output, .. }) => Some(output.clone()),
             Err(_) => None,
         }
     }
@@ -29,13 +30,151 @@ impl JsCompileResult {
     #[wasm_bindgen(js_name = "getSourceMap")]
     pub fn source_map(&self) -> Option<Vec<u8>> {
         match &self.0 {
-            Ok(CompiledTemplate {
-                output: _,
-                source_map,
-            }) => Some(source_map.clone()),
+            Ok(CompiledTemplate { source_map, .. }) => Some(source_map.clone()),
             Err(_) => None,
         }
     }
+
+    /// Get the messages translated with fallback locales.
+    #[wasm_bindgen(js_name = "getFallbacks")]
+    pub fn fallbacks(&self) -> Vec<String> {
+        match &self.0 {
+            Ok(CompiledTemplate { fallbacks, .. }) => {
+                fallbacks.iter().map(|x| x.to_string()).collect()
+            }
+            Err(_) => vec![],
+        }
+    }
+
+    /// Get the message table of the lookup-table strategy as JSON.
+    #[wasm_bindgen(js_name = "getMessageTable")]
+    pub fn message_table(&self) -> Option<String> {
+        match &self.0 {
+            Ok(CompiledTemplate {
+                message_table: Some(table),
+                ..
+            }) => Some(table.to_json()),
+            _ => None,
+        }
+    }
+
+    /// Get the message table of the lookup-table strategy as a WXS module.
+    #[wasm_bindgen(js_name = "getMessageModule")]
+    pub fn message_module(&self) -> Option<String> {
+        match &self.0 {
+            Ok(CompiledTemplate {
+                message_table: Some(table),
+                ..
+            }) => Some(table.to_wxs()),
+            _ => None,
+        }
+    }
+
+    #[wasm_bindgen(js_name = "getError")]
+    pub fn error(&self) -> Option<String> {
+        match &self.0 {
+            Ok(_) => None,
+            Err(err) => Some(err.to_string()),
+        }
+    }
+
+    #[wasm_bindgen(js_name = "getWarnings")]
+    pub fn warnings(&self) -> Vec<String> {
+        match &self.0 {
+            Ok(CompiledTemplate { warnings, .. }) => {
+                warnings.iter().map(|x| x.to_string()).collect()
+            }
+            Err(_) => vec![],
+        }
+    }
+}
+
+/// Options of the compilation, see [`CompileOptions`].
+#[wasm_bindgen(js_name = "CompileOptions")]
+#[derive(Default)]
+pub struct JsCompileOptions(CompileOptions);
+
+#[wasm_bindgen(js_class = "CompileOptions")]
+impl JsCompileOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the rules of the attributes to translate, like `title`, `my-dialog:title` or `!my-dialog:title`.
+    #[wasm_bindgen(js_name = "setAttributes")]
+    pub fn set_attributes(&mut self, attributes: Vec<String>) {
+        self.0.included_attributes = attributes;
+    }
+
+    /// Set the strategy: `branches` (the default), `fine-grained` or `lookup-table`.
+    #[wasm_bindgen(js_name = "setStrategy")]
+    pub fn set_strategy(&mut self, strategy: &str) -> Result<(), JsError> {
+        self.0.strategy = strategy.parse().map_err(|err: String| JsError::new(&err))?;
+        Ok(())
+    }
+
+    /// Set the name and the `src` of the WXS module used by the lookup-table strategy.
+    #[wasm_bindgen(js_name = "setMessageModule")]
+    pub fn set_message_module(&mut self, name: String, src: Option<String>) {
+        self.0.message_module_name = name;
+        self.0.message_module_src = src;
+    }
+
+    /// Add a locale fallback chain like `zh-hk -> zh-tw -> zh`.
+    #[wasm_bindgen(js_name = "addFallback")]
+    pub fn add_fallback(&mut self, chain: &str) {
+        self.0.locale_fallbacks.add_chain(chain);
+    }
+
+    /// Set the expression of the current locale (`locale` by default).
+    #[wasm_bindgen(js_name = "setLocaleExpression")]
+    pub fn set_locale_expression(&mut self, expression: String) {
+        self.0.locale_expression = expression;
+    }
+
+    /// Set how the placeholders in messages are named: `named` (the default) or `letters`.
+    #[wasm_bindgen(js_name = "setPlaceholderStyle")]
+    pub fn set_placeholder_style(&mut self, style: &str) -> Result<(), JsError> {
+        self.0.placeholder_style = style.parse().map_err(|err: String| JsError::new(&err))?;
+        Ok(())
+    }
+
+    /// Set how the whitespace of texts is normalized into msgids: `trim` (the default), `collapse` or `preserve`.
+    #[wasm_bindgen(js_name = "setWhitespace")]
+    pub fn set_whitespace(&mut self, policy: &str) -> Result<(), JsError> {
+        self.0.whitespace = policy.parse().map_err(|err: String| JsError::new(&err))?;
+        Ok(())
+    }
+
+    /// Set which string literals inside data bindings are translated: `ignore` (the default), `branches` or `all`.
+    #[wasm_bindgen(js_name = "setLiterals")]
+    pub fn set_literals(&mut self, policy: &str) -> Result<(), JsError> {
+        self.0.literals = policy.parse().map_err(|err: String| JsError::new(&err))?;
+        Ok(())
+    }
+
+    /// Set how the translations with mismatched placeholders are reported: `warning` (the default) or `error`.
+    #[wasm_bindgen(js_name = "setPlaceholderCheck")]
+    pub fn set_placeholder_check(&mut self, check: &str) -> Result<(), JsError> {
+        self.0.placeholder_check = check.parse().map_err(|err: String| JsError::new(&err))?;
+        Ok(())
+    }
+
+    /// Set the syntax of the translations: `plain` (the default) or `icu`.
+    #[wasm_bindgen(js_name = "setMessageFormat")]
+    pub fn set_message_format(&mut self, format: &str) -> Result<(), JsError> {
+        self.0.message_format = format.parse().map_err(|err: String| JsError::new(&err))?;
+        Ok(())
+    }
+
+    /// Whether to add the locale to the data of `<template is>` call sites which do not pass it (`true` by default).
+    ///
+    /// If disabled, these call sites are reported as warnings instead.
+    #[wasm_bindgen(js_name = "setForwardLocale")]
+    pub fn set_forward_locale(&mut self, forward_locale: bool) {
+        self.0.forward_locale = forward_locale;
+    }
 }
 
 #[wasm_bindgen(js_name = "compile")]
@@ -49,8 +188,169 @@ pub fn js_compile(
     JsCompileResult(r)
 }
 
+/// Compile with PO catalogs.
+///
+/// `locales` and `po_sources` are parallel lists; a `global.` prefixed locale marks a global catalog.
+/// `po_paths` are the file names of the catalogs used in diagnostics, `<locale>.po` by default.
+#[wasm_bindgen(js_name = "compilePo")]
+pub fn js_compile_po(
+    path: &str,
+    source: &str,
+    locales: Vec<String>,
+    po_sources: Vec<String>,
+    attributes: Vec<String>,
+    po_paths: Option<Vec<String>>,
+) -> JsCompileResult {
+    let r = parse_po_sources(path, locales, &po_sources, po_paths)
+        .and_then(|catalogs| compile_po(path, source, &catalogs, &attributes));
+    JsCompileResult(r)
+}
+
+/// Compile with a TOML translation file and options.
+#[wasm_bindgen(js_name = "compileWithOptions")]
+pub fn js_compile_with_options(
+    path: &str,
+    source: &str,
+    trans_source: &str,
+    options: &JsCompileOptions,
+) -> JsCompileResult {
+    let r = TransContent::from_toml(path, trans_source)
+        .and_then(|trans_content| compile_with_options(path, source, &trans_content, &options.0));
+    JsCompileResult(r)
+}
+
+/// Compile with PO catalogs and options.
+#[wasm_bindgen(js_name = "compilePoWithOptions")]
+pub fn js_compile_po_with_options(
+    path: &str,
+    source: &str,
+    locales: Vec<String>,
+    po_sources: Vec<String>,
+    options: &JsCompileOptions,
+    po_paths: Option<Vec<String>>,
+) -> JsCompileResult {
+    let r = parse_po_sources(path, locales, &po_sources, po_paths).and_then(|catalogs| {
+        let trans_content = TransContent::from_po_catalogs(&catalogs);
+        compile_with_options(path, source, &trans_content, &options.0)
+    });
+    JsCompileResult(r)
+}
+
+fn parse_po_sources(
+    path: &str,
+    locales: Vec<String>,
+    po_sources: &[String],
+    po_paths: Option<Vec<String>>,
+) -> Result<Vec<(String, PoCatalog)>, Diagnostic> {
+    let po_paths = po_paths.unwrap_or_else(|| {
+        locales
+            .iter()
+            .map(|locale| format!("{}.po", locale.strip_prefix("global.").unwrap_or(locale)))
+            .collect()
+    });
+    if po_sources.len() != locales.len() || po_paths.len() != locales.len() {
+        return Err(Diagnostic::without_location(
+            DiagnosticKind::InvalidOption,
+            path,
+            format!(
+                "Expected a PO source and a path for each of the {} locales, got {} sources and {} paths",
+                locales.len(),
+                po_sources.len(),
+                po_paths.len(),
+            ),
+        ));
+    }
+    locales
+        .into_iter()
+        .zip(po_sources.iter().zip(po_paths.iter()))
+        .map(|(locale, (po_source, po_path))| {
+            let catalog = parse_po(po_path, po_source)?;
+            Ok((locale, catalog))
+        })
+        .collect()
+}
+
 #[wasm_bindgen]
-pub struct JsUntranslatedTerms(Result<UntranslatedTerms, String>);
+pub struct JsCompiledLocales(Result<CompiledLocales, Diagnostic>);
+
+impl JsCompiledLocales {
+    fn get(&self, locale: Option<String>) -> Option<&CompiledTemplate> {
+        let r = self.0.as_ref().ok()?;
+        match locale {
+            None => Some(&r.fallback),
+            Some(locale) => r.locales.iter().find(|x| x.0 == locale).map(|x| &x.1),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl JsCompiledLocales {
+    #[wasm_bindgen(js_name = "isSuccess")]
+    pub fn success(&self) -> bool {
+        self.0.is_ok()
+    }
+
+    /// Get the locales which have a separate output.
+    #[wasm_bindgen(js_name = "getLocales")]
+    pub fn locales(&self) -> Vec<String> {
+        match &self.0 {
+            Ok(r) => r.locales.iter().map(|x| x.0.clone()).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Get the output of the locale, or the source language one if `locale` is not given.
+    #[wasm_bindgen(js_name = "getOutput")]
+    pub fn output(&self, locale: Option<String>) -> Option<String> {
+        self.get(locale).map(|x| x.output.clone())
+    }
+
+    #[wasm_bindgen(js_name = "getSourceMap")]
+    pub fn source_map(&self, locale: Option<String>) -> Option<Vec<u8>> {
+        self.get(locale).map(|x| x.source_map.clone())
+    }
+
+    #[wasm_bindgen(js_name = "getError")]
+    pub fn error(&self) -> Option<String> {
+        match &self.0 {
+            Ok(_) => None,
+            Err(err) => Some(err.to_string()),
+        }
+    }
+
+    #[wasm_bindgen(js_name = "getWarnings")]
+    pub fn warnings(&self) -> Vec<String> {
+        match &self.0 {
+            Ok(r) => std::iter::once(&r.warnings)
+                .chain(std::iter::once(&r.fallback.warnings))
+                .chain(r.locales.iter().map(|x| &x.1.warnings))
+                .flatten()
+                .map(|x| x.to_string())
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+}
+
+/// Compile with PO catalogs into a separate output for each locale.
+#[wasm_bindgen(js_name = "compilePoPerLocale")]
+pub fn js_compile_po_per_locale(
+    path: &str,
+    source: &str,
+    locales: Vec<String>,
+    po_sources: Vec<String>,
+    options: &JsCompileOptions,
+    po_paths: Option<Vec<String>>,
+) -> JsCompiledLocales {
+    let r = parse_po_sources(path, locales, &po_sources, po_paths).and_then(|catalogs| {
+        let trans_content = TransContent::from_po_catalogs(&catalogs);
+        compile_per_locale(path, source, &trans_content, &options.0)
+    });
+    JsCompiledLocales(r)
+}
+
+#[wasm_bindgen]
+pub struct JsUntranslatedTerms(Result<UntranslatedTerms, Diagnostic>);
 
 #[wasm_bindgen]
 impl JsUntranslatedTerms {
@@ -65,10 +365,47 @@ impl JsUntranslatedTerms {
     #[wasm_bindgen(js_name = "getOutput")]
     pub fn output(&self) -> Option<Vec<String>> {
         match &self.0 {
-            Ok(UntranslatedTerms { output }) => Some(output.clone()),
+            Ok(UntranslatedTerms { output, .. }) => {
+                Some(output.iter().map(|x| x.term.clone()).collect())
+            }
             Err(_) => None,
         }
     }
+
+    /// Get the PO file content, with `placeholder` as the msgstr of each entry.
+    #[wasm_bindgen(js_name = "getPo")]
+    pub fn po(&self, placeholder: Option<String>) -> Option<String> {
+        match &self.0 {
+            Ok(terms) => {
+                let mut catalog = terms.to_catalog();
+                if let Some(placeholder) = &placeholder {
+                    for entry in &mut catalog.entries {
+                        entry.msgstr = vec![placeholder.clone()];
+                    }
+                }
+                Some(write_po(&catalog))
+            }
+            Err(_) => None,
+        }
+    }
+
+    #[wasm_bindgen(js_name = "getError")]
+    pub fn error(&self) -> Option<String> {
+        match &self.0 {
+            Ok(_) => None,
+            Err(err) => Some(err.to_string()),
+        }
+    }
+
+    #[wasm_bindgen(js_name = "getWarnings")]
+    pub fn warnings(&self) -> Vec<String> {
+        match &self.0 {
+            Ok(UntranslatedTerms { warnings, .. }) => {
+                warnings.iter().map(|x| x.to_string()).collect()
+            }
+            Err(_) => vec![],
+        }
+    }
 }
 
 #[wasm_bindgen(js_name = "search")]
@@ -76,3 +413,20 @@ pub fn js_search(path: &str, source: &str, attributes: Vec<String>) -> JsUntrans
     let r = search(path, source, &attributes);
     JsUntranslatedTerms(r)
 }
+
+/// Search with the attributes, the placeholder style, the whitespace policy and the literal policy of the compile options.
+#[wasm_bindgen(js_name = "searchWithOptions")]
+pub fn js_search_with_options(
+    path: &str,
+    source: &str,
+    options: &JsCompileOptions,
+) -> JsUntranslatedTerms {
+    let options = SearchOptions {
+        included_attributes: options.0.included_attributes.clone(),
+        placeholder_style: options.0.placeholder_style,
+        whitespace: options.0.whitespace,
+        literals: options.0.literals,
+    };
+    let r = search_with_options(path, source, &options);
+    JsUntranslatedTerms(r)
+}
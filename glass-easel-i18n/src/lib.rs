@@ -3,12 +3,26 @@ use glass_easel_template_compiler::parse::{
     Template,
 };
 
+mod attribute;
 mod compile;
+mod diagnostic;
+mod extract;
 mod js_bindings;
+mod literal;
+mod placeholder;
+mod po;
 mod search;
+mod whitespace;
 
+pub use attribute::{is_attribute_included, AttributeRule};
 pub use compile::*;
+pub use diagnostic::{Diagnostic, DiagnosticKind};
+pub use extract::*;
+pub use literal::LiteralPolicy;
+pub use placeholder::PlaceholderStyle;
+pub use po::*;
 pub use search::*;
+pub use whitespace::WhitespacePolicy;
 
 fn parse_additional_template(src: &str) -> Template {
     let (template, _) = glass_easel_template_compiler::parse::parse("", src);
@@ -33,6 +47,7 @@ fn has_i18n_translate_children(tag: &UnknownMetaTag) -> bool {
     get_i18n_attr_value(tag, "translate-children").is_some()
 }
 
+#[allow(clippy::single_match)]
 pub fn contains_i18n_translate_children(node_list: &Vec<Node>) -> bool {
     for node in node_list {
         match node {
@@ -47,3 +62,20 @@ pub fn contains_i18n_translate_children(node_list: &Vec<Node>) -> bool {
     }
     false
 }
+
+pub fn contains_i18n_tag(node_list: &[Node]) -> bool {
+    for node in node_list {
+        if let Node::UnknownMetaTag(tag, ..) = node {
+            if is_i18n_tag(tag) {
+                return true;
+            }
+            break;
+        }
+    }
+    false
+}
+
+/// Whether the template source is declared with `<!I18N>`.
+pub fn is_i18n_template(source: &str) -> bool {
+    contains_i18n_tag(&parse_additional_template(source).content)
+}
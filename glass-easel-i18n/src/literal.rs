@@ -0,0 +1,104 @@
+use crate::placeholder::is_concatenation;
+use glass_easel_template_compiler::parse::expr::Expression;
+use std::str::FromStr;
+
+/// Which string literals inside data bindings are translated, besides the literal parts of texts.
+///
+/// Each literal is a message of its own, like `成功` and `失败` in `{{ ok ? "成功" : "失败" }}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LiteralPolicy {
+    /// Leave the literals inside data bindings as is.
+    #[default]
+    Ignore,
+    /// Only the literals which can be the value of a binding,
+    /// i.e. the branches of `?:`, and the results of `&&`, `||` and `??`.
+    Branches,
+    /// All literals, including the ones in function arguments, arrays and objects,
+    /// except the conditions, the operands of comparisons and the keys of `a["key"]`.
+    All,
+}
+
+impl FromStr for LiteralPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "branches" => Ok(Self::Branches),
+            "all" => Ok(Self::All),
+            _ => Err(format!("Unknown literal policy: {}", s)),
+        }
+    }
+}
+
+impl LiteralPolicy {
+    /// The translatable string literals in the placeholders of a value expression, in source order.
+    ///
+    /// The literal parts of the value itself belong to the message of the value, so they are not included.
+    pub(crate) fn literals(self, expr: &mut Expression) -> Vec<&mut Expression> {
+        let mut ret = vec![];
+        if self != Self::Ignore {
+            placeholder_literals(expr, self, &mut ret);
+        }
+        ret
+    }
+}
+
+fn placeholder_literals<'a>(
+    expr: &'a mut Expression,
+    policy: LiteralPolicy,
+    ret: &mut Vec<&'a mut Expression>,
+) {
+    match expr {
+        Expression::ToStringWithoutUndefined { value, .. } => binding_literals(value, policy, ret),
+        Expression::Plus { .. } if !is_concatenation(expr) => binding_literals(expr, policy, ret),
+        Expression::Plus { left, right, .. } => {
+            placeholder_literals(left, policy, ret);
+            placeholder_literals(right, policy, ret);
+        }
+        Expression::LitStr { .. } => {}
+        // a value of a single data binding is not converted to a string
+        _ => binding_literals(expr, policy, ret),
+    }
+}
+
+fn binding_literals<'a>(
+    expr: &'a mut Expression,
+    policy: LiteralPolicy,
+    ret: &mut Vec<&'a mut Expression>,
+) {
+    if matches!(expr, Expression::LitStr { value, .. } if !value.trim().is_empty()) {
+        ret.push(expr);
+        return;
+    }
+    match expr {
+        Expression::Cond {
+            true_br, false_br, ..
+        } => {
+            binding_literals(true_br, policy, ret);
+            binding_literals(false_br, policy, ret);
+        }
+        Expression::LogicAnd { right, .. } => binding_literals(right, policy, ret),
+        Expression::LogicOr { left, right, .. }
+        | Expression::NullishCoalescing { left, right, .. } => {
+            binding_literals(left, policy, ret);
+            binding_literals(right, policy, ret);
+        }
+        _ if policy != LiteralPolicy::All => {}
+        Expression::Lt { .. }
+        | Expression::Gt { .. }
+        | Expression::Lte { .. }
+        | Expression::Gte { .. }
+        | Expression::InstanceOf { .. }
+        | Expression::Eq { .. }
+        | Expression::Ne { .. }
+        | Expression::EqFull { .. }
+        | Expression::NeFull { .. } => {}
+        Expression::DynamicMember { obj, .. } => binding_literals(obj, policy, ret),
+        _ => {
+            for sub in expr.sub_expressions_mut() {
+                binding_literals(sub, policy, ret);
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use std::{path::PathBuf, process::ExitCode};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 use clap::{Parser, Subcommand};
 use glass_easel_i18n::*;
@@ -8,6 +11,15 @@ use glass_easel_i18n::*;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// How the placeholders in messages are named: `letters` as generated by earlier versions, or `named`
+    #[arg(long, global = true, default_value = "letters")]
+    placeholder_style: PlaceholderStyle,
+    /// How the whitespace of texts is normalized into msgids: `trim`, `collapse` or `preserve`
+    #[arg(long, global = true, default_value = "trim")]
+    whitespace: WhitespacePolicy,
+    /// Which string literals inside data bindings are translated: `ignore`, `branches` or `all`
+    #[arg(long, global = true, default_value = "ignore")]
+    literals: LiteralPolicy,
 }
 
 #[derive(Subcommand)]
@@ -16,20 +28,238 @@ enum Commands {
     Compile {
         /// Path of the tamplate file
         path: PathBuf,
+        /// Directory of the global PO catalogs, e.g. `src/locale`
+        #[arg(long)]
+        global_locale: Option<PathBuf>,
+        /// Write a separate template for each locale into the directory instead
+        ///
+        /// The outputs are named `<name>.<locale>.wxml`, and the source language one is `<name>.wxml`.
+        /// A source map is written next to each output with an additional `.map` extension.
+        #[arg(long)]
+        per_locale: Option<PathBuf>,
+        /// How the translations are combined into a single output: `branches`, `fine-grained` or `lookup-table`
+        #[arg(long, default_value = "branches")]
+        strategy: CompileStrategy,
+        /// Path of the WXS module generated by the `lookup-table` strategy, relative to the template
+        ///
+        /// The output refers to the module with this path. A `.json` path writes the table as JSON instead.
+        #[arg(long)]
+        message_module: Option<String>,
+        /// A locale fallback chain like `zh-hk -> zh-tw -> zh` (can be given multiple times)
+        #[arg(long)]
+        fallback: Vec<String>,
+        /// Print the messages translated with fallback locales
+        #[arg(long)]
+        report_fallbacks: bool,
+        /// The expression of the current locale, e.g. `app.settings.lang`
+        #[arg(long, default_value = "locale")]
+        locale_expression: String,
+        /// Only report the `<template is>` call sites not passing the locale instead of adding it to their data
+        #[arg(long)]
+        no_forward_locale: bool,
+        /// How the translations with mismatched placeholders are reported: `warning` or `error`
+        #[arg(long, default_value = "warning")]
+        placeholder_check: PlaceholderCheck,
+        /// The syntax of the translations: `plain` or `icu`
+        #[arg(long, default_value = "plain")]
+        message_format: MessageFormat,
     },
-    /// Search the untranslated terms
+    /// Search the untranslated terms and generate a POT file
     Search {
         /// Path of the tamplate file
         path: PathBuf,
-        /// Place holder of the untranslated terms
-        placeholder: String,
+        /// Path of the output file (print to stdout if not given)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Place holder of the untranslated terms (empty if not given)
+        placeholder: Option<String>,
+        /// Place holder of the untranslated terms, the same as the positional one
+        #[arg(
+            short = 'p',
+            long = "placeholder",
+            id = "placeholder_option",
+            value_name = "PLACEHOLDER",
+            conflicts_with = "placeholder"
+        )]
+        placeholder_option: Option<String>,
     },
+    /// Search the untranslated terms of all I18N templates in a project
+    ///
+    /// A `messages.pot` is written into the `<name>.locale` directory of each template,
+    /// and the one of the terms shared by several templates into the global locale directory.
+    /// The existing PO files in them are merged.
+    SearchProject {
+        /// The source root, e.g. `src`
+        root: PathBuf,
+        /// Directory of the global PO catalogs (`<root>/locale` if not given)
+        #[arg(long)]
+        global_locale: Option<PathBuf>,
+    },
+    /// Merge the terms of the template into an existing locale catalog
+    Merge {
+        /// Path of the tamplate file
+        path: PathBuf,
+        /// Path of the locale PO file (created if not exists)
+        catalog: PathBuf,
+        /// Path of the output file (overwrite the catalog if not given)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Read all PO catalogs in a directory, using the file names as locales.
+fn read_po_dir(
+    dir: &Path,
+    locale_prefix: &str,
+    catalogs: &mut Vec<(String, PoCatalog)>,
+) -> Result<(), String> {
+    let read_dir = std::fs::read_dir(dir)
+        .map_err(|err| format!("Failed to read locale directory: {}", err))?;
+    let mut paths: Vec<PathBuf> = read_dir
+        .filter_map(|entry| entry.ok().map(|x| x.path()))
+        .filter(|path| path.extension().is_some_and(|x| x == "po"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let Some(locale) = path.file_stem().and_then(|x| x.to_str()) else {
+            continue;
+        };
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read PO file: {}", err))?;
+        let catalog = parse_po(&path.to_string_lossy(), &source).map_err(|err| err.to_string())?;
+        catalogs.push((format!("{}{}", locale_prefix, locale), catalog));
+    }
+    Ok(())
+}
+
+/// Write compiled templates and their source maps into a directory.
+fn write_compiled_templates(
+    dir: &Path,
+    outputs: &[(String, &CompiledTemplate)],
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("Failed to create output directory: {}", err))?;
+    for (name, compiled) in outputs {
+        for warning in &compiled.warnings {
+            eprintln!("{}", warning);
+        }
+        std::fs::write(dir.join(name), &compiled.output)
+            .map_err(|err| format!("Failed to write output file: {}", err))?;
+        std::fs::write(dir.join(format!("{}.map", name)), &compiled.source_map)
+            .map_err(|err| format!("Failed to write output file: {}", err))?;
+    }
+    Ok(())
+}
+
+/// The file name of the template catalogs generated in locale directories.
+const TEMPLATE_CATALOG_NAME: &str = "messages.pot";
+
+/// Find all WXML files in a directory recursively.
+fn find_wxml_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let read_dir =
+        std::fs::read_dir(dir).map_err(|err| format!("Failed to read directory: {}", err))?;
+    let mut paths: Vec<PathBuf> = read_dir.filter_map(|x| x.ok().map(|x| x.path())).collect();
+    paths.sort();
+    for path in paths {
+        let Some(name) = path.file_name().and_then(|x| x.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') || name == "node_modules" {
+            continue;
+        }
+        if path.is_dir() {
+            find_wxml_files(&path, files)?;
+        } else if path.extension().is_some_and(|x| x == "wxml") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Write the template catalog into a locale directory and merge it into the existing PO files.
+fn update_locale_dir(dir: &Path, template: &PoCatalog) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("Failed to create locale directory: {}", err))?;
+    std::fs::write(dir.join(TEMPLATE_CATALOG_NAME), write_po(template))
+        .map_err(|err| format!("Failed to write output file: {}", err))?;
+    let mut catalogs = vec![];
+    read_po_dir(dir, "", &mut catalogs)?;
+    for (locale, existing) in catalogs {
+        let merged = merge_catalog(template, &existing);
+        std::fs::write(dir.join(format!("{}.po", locale)), write_po(&merged))
+            .map_err(|err| format!("Failed to write output file: {}", err))?;
+    }
+    Ok(())
+}
+
+fn search_project(
+    root: &Path,
+    global_locale: &Path,
+    options: &SearchOptions,
+) -> Result<(), String> {
+    let mut files = vec![];
+    find_wxml_files(root, &mut files)?;
+    let mut all_terms = vec![];
+    for path in files {
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read source file: {}", err))?;
+        if !is_i18n_template(&source) {
+            continue;
+        }
+        let relative_path = path.strip_prefix(root).unwrap_or(&path);
+        let relative_path = relative_path
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let terms =
+            search_with_options(&relative_path, &source, options).map_err(|err| err.to_string())?;
+        for warning in &terms.warnings {
+            eprintln!("{}", warning);
+        }
+        update_locale_dir(&path.with_extension("locale"), &terms.to_catalog())?;
+        all_terms.push(terms);
+    }
+    update_locale_dir(global_locale, &shared_terms_catalog(&all_terms))
+}
+
+/// Generate the global catalog with the terms used by more than one template.
+fn shared_terms_catalog(all_terms: &[UntranslatedTerms]) -> PoCatalog {
+    let mut catalog = terms_to_catalog(all_terms);
+    catalog.entries.retain(|entry| {
+        let is_used = |terms: &&UntranslatedTerms| {
+            terms
+                .output
+                .iter()
+                .any(|x| x.term == entry.msgid && x.context == entry.msgctxt)
+        };
+        all_terms.iter().filter(is_used).count() > 1
+    });
+    catalog
 }
 
 fn main() -> ExitCode {
     let cli = Cli::parse();
+    let search_options = SearchOptions {
+        placeholder_style: cli.placeholder_style,
+        whitespace: cli.whitespace,
+        literals: cli.literals,
+        ..Default::default()
+    };
     match cli.command {
-        Commands::Compile { path } => {
+        Commands::Compile {
+            path,
+            global_locale,
+            per_locale,
+            strategy,
+            message_module,
+            fallback,
+            report_fallbacks,
+            locale_expression,
+            no_forward_locale,
+            placeholder_check,
+            message_format,
+        } => {
             let Some(file_name) = path.file_name() else {
                 eprintln!("Not a file");
                 return ExitCode::FAILURE;
@@ -45,16 +275,109 @@ fn main() -> ExitCode {
                     return ExitCode::FAILURE;
                 }
             };
-            let trans_source_path = path.with_extension("toml");
-            let trans_source = match std::fs::read_to_string(&trans_source_path) {
-                Ok(source) => source,
-                Err(err) => {
-                    eprintln!("Failed to read translate TOML file: {}", err);
-                    return ExitCode::FAILURE;
+            let locale_dir = path.with_extension("locale");
+            let trans_content = if global_locale.is_some() || locale_dir.is_dir() {
+                let mut catalogs = vec![];
+                if let Some(global_locale) = &global_locale {
+                    if let Err(err) = read_po_dir(global_locale, "global.", &mut catalogs) {
+                        eprintln!("{}", err);
+                        return ExitCode::FAILURE;
+                    }
+                }
+                if locale_dir.is_dir() {
+                    if let Err(err) = read_po_dir(&locale_dir, "", &mut catalogs) {
+                        eprintln!("{}", err);
+                        return ExitCode::FAILURE;
+                    }
+                }
+                TransContent::from_po_catalogs(&catalogs)
+            } else {
+                let trans_source_path = path.with_extension("toml");
+                let trans_source = match std::fs::read_to_string(&trans_source_path) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        eprintln!("Failed to read translate TOML file: {}", err);
+                        return ExitCode::FAILURE;
+                    }
+                };
+                match TransContent::from_toml(&trans_source_path.to_string_lossy(), &trans_source) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        return ExitCode::FAILURE;
+                    }
                 }
             };
-            match compile(file_name, &source, &trans_source, &[]) {
+            let mut locale_fallbacks = LocaleFallbacks::default();
+            for chain in &fallback {
+                locale_fallbacks.add_chain(chain);
+            }
+            let options = CompileOptions {
+                strategy,
+                locale_fallbacks,
+                locale_expression,
+                forward_locale: !no_forward_locale,
+                placeholder_style: cli.placeholder_style,
+                whitespace: cli.whitespace,
+                literals: cli.literals,
+                placeholder_check,
+                message_format,
+                message_module_src: message_module.clone().filter(|x| !x.ends_with(".json")),
+                ..Default::default()
+            };
+            if let Some(out_dir) = &per_locale {
+                let r = match compile_per_locale(file_name, &source, &trans_content, &options) {
+                    Ok(r) => r,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        return ExitCode::FAILURE;
+                    }
+                };
+                for warning in &r.warnings {
+                    eprintln!("{}", warning);
+                }
+                if report_fallbacks {
+                    for (_, compiled) in &r.locales {
+                        for usage in &compiled.fallbacks {
+                            eprintln!("{}", usage);
+                        }
+                    }
+                }
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let mut outputs = vec![(format!("{}.wxml", stem), &r.fallback)];
+                for (locale, compiled) in &r.locales {
+                    outputs.push((format!("{}.{}.wxml", stem, locale), compiled));
+                }
+                if let Err(err) = write_compiled_templates(out_dir, &outputs) {
+                    eprintln!("{}", err);
+                    return ExitCode::FAILURE;
+                }
+                return ExitCode::SUCCESS;
+            }
+            match compile_with_options(file_name, &source, &trans_content, &options) {
                 Ok(r) => {
+                    for warning in r.warnings {
+                        eprintln!("{}", warning);
+                    }
+                    if report_fallbacks {
+                        for usage in &r.fallbacks {
+                            eprintln!("{}", usage);
+                        }
+                    }
+                    if let (Some(table), Some(message_module)) = (&r.message_table, &message_module)
+                    {
+                        let content = if message_module.ends_with(".json") {
+                            table.to_json()
+                        } else {
+                            table.to_wxs()
+                        };
+                        let module_path =
+                            path.parent().unwrap_or(Path::new("")).join(message_module);
+                        if let Err(err) = std::fs::write(module_path, content) {
+                            eprintln!("Failed to write output file: {}", err);
+                            return ExitCode::FAILURE;
+                        }
+                    }
                     println!("{}", r.output);
                 }
                 Err(err) => {
@@ -63,7 +386,13 @@ fn main() -> ExitCode {
                 }
             }
         }
-        Commands::Search { path, placeholder } => {
+        Commands::Search {
+            path,
+            output,
+            placeholder,
+            placeholder_option,
+        } => {
+            let placeholder = placeholder.or(placeholder_option);
             let Some(file_name) = path.file_name() else {
                 eprintln!("Not a file");
                 return ExitCode::FAILURE;
@@ -79,15 +408,27 @@ fn main() -> ExitCode {
                     return ExitCode::FAILURE;
                 }
             };
-            match search(file_name, &source, &[]) {
+            match search_with_options(file_name, &source, &search_options) {
                 Ok(untranslated_terms) => {
-                    println!("{:#?}", untranslated_terms.output);
-                    let mut po_terms = String::new();
-                    for term in untranslated_terms.output {
-                        let po_term = format!("msgid \"{}\"\nmsgstr \"{}\"\n\n", term, placeholder);
-                        po_terms.push_str(&po_term);
+                    for warning in &untranslated_terms.warnings {
+                        eprintln!("{}", warning);
+                    }
+                    let mut catalog = untranslated_terms.to_catalog();
+                    if let Some(placeholder) = &placeholder {
+                        for entry in &mut catalog.entries {
+                            entry.msgstr = vec![placeholder.clone()];
+                        }
+                    }
+                    let po = write_po(&catalog);
+                    match &output {
+                        Some(output) => {
+                            if let Err(err) = std::fs::write(output, po) {
+                                eprintln!("Failed to write output file: {}", err);
+                                return ExitCode::FAILURE;
+                            }
+                        }
+                        None => print!("{}", po),
                     }
-                    println!("{}", po_terms);
                 }
                 Err(err) => {
                     eprintln!("{}", err);
@@ -95,6 +436,73 @@ fn main() -> ExitCode {
                 }
             }
         }
+        Commands::SearchProject {
+            root,
+            global_locale,
+        } => {
+            let global_locale = global_locale.unwrap_or_else(|| root.join("locale"));
+            if let Err(err) = search_project(&root, &global_locale, &search_options) {
+                eprintln!("{}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+        Commands::Merge {
+            path,
+            catalog,
+            output,
+        } => {
+            let Some(file_name) = path.file_name() else {
+                eprintln!("Not a file");
+                return ExitCode::FAILURE;
+            };
+            let Some(file_name) = file_name.to_str() else {
+                eprintln!("Not a UTF-8 file name");
+                return ExitCode::FAILURE;
+            };
+            let source = match std::fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(err) => {
+                    eprintln!("Failed to read source file: {}", err);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let existing = if catalog.exists() {
+                let po_source = match std::fs::read_to_string(&catalog) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        eprintln!("Failed to read PO file: {}", err);
+                        return ExitCode::FAILURE;
+                    }
+                };
+                match parse_po(&catalog.to_string_lossy(), &po_source) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        return ExitCode::FAILURE;
+                    }
+                }
+            } else {
+                let language = catalog.file_stem().and_then(|x| x.to_str());
+                PoCatalog::with_header(language, None)
+            };
+            let untranslated_terms = match search_with_options(file_name, &source, &search_options)
+            {
+                Ok(x) => x,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return ExitCode::FAILURE;
+                }
+            };
+            for warning in &untranslated_terms.warnings {
+                eprintln!("{}", warning);
+            }
+            let merged = merge_catalog(&untranslated_terms.to_catalog(), &existing);
+            let output = output.as_ref().unwrap_or(&catalog);
+            if let Err(err) = std::fs::write(output, write_po(&merged)) {
+                eprintln!("Failed to write output file: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
     };
     ExitCode::SUCCESS
 }
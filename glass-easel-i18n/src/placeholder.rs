@@ -0,0 +1,391 @@
+use glass_easel_template_compiler::parse::{
+    expr::Expression,
+    tag::{Comment, ElementKind, Node, NormalAttribute, UnknownMetaTag, Value},
+};
+use std::str::FromStr;
+
+use crate::{
+    get_i18n_attr_value, has_i18n_translate_children, is_i18n_tag, Diagnostic, DiagnosticKind,
+};
+
+/// The attribute of an element which sets the message context of its attributes and descendants.
+pub(crate) const CONTEXT_ATTR: &str = "i18n-context";
+
+/// The attribute of an element which excludes it and its descendants from the translation.
+pub(crate) const IGNORE_ATTR: &str = "i18n-ignore";
+
+/// How the placeholders in messages are named.
+///
+/// The letters are kept as the default so that the existing translations still match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaceholderStyle {
+    /// Named after the data path of the binding like `{{count}}` or `{{user.name}}`,
+    /// or the tag name of an element in a translate-children group like `{{span}}`.
+    ///
+    /// Other bindings are named with the letter of their position.
+    /// Repeated bindings of the same data path share a placeholder.
+    Named,
+    /// `{{A}}`, `{{B}}`, ... in order, as generated by earlier versions.
+    #[default]
+    Letters,
+}
+
+impl FromStr for PlaceholderStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "named" => Ok(Self::Named),
+            "letters" => Ok(Self::Letters),
+            _ => Err(format!("Unknown placeholder style: {}", s)),
+        }
+    }
+}
+
+/// The letter name of the placeholder at `index`: `A` to `Z`, and then `AA`, `AB`, ...
+fn letter_name(index: usize) -> String {
+    let mut ret = vec![];
+    let mut n = index + 1;
+    while n > 0 {
+        n -= 1;
+        ret.push((b'A' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    ret.into_iter().rev().collect()
+}
+
+/// The data path of an expression like `user.name`.
+fn expression_path(expr: &Expression) -> Option<String> {
+    match expr {
+        Expression::DataField { name, .. } => Some(name.to_string()),
+        Expression::StaticMember {
+            obj, field_name, ..
+        } => Some(format!("{}.{}", expression_path(obj)?, field_name)),
+        _ => None,
+    }
+}
+
+/// Allocates the placeholder names of a single message.
+#[derive(Debug, Clone)]
+pub(crate) struct PlaceholderNames {
+    style: PlaceholderStyle,
+    /// The names given by `<!I18N placeholders="...">`, in order.
+    explicit: Vec<String>,
+    /// The allocated names, and whether each one can be shared by repeated bindings.
+    names: Vec<(String, bool)>,
+    /// The placeholder given by `<!I18N plural="...">`, whose value selects the plural form.
+    plural: Option<String>,
+    /// The message context (msgctxt) given by `<!I18N context="...">` or inherited from the elements.
+    context: Option<String>,
+}
+
+impl PlaceholderNames {
+    fn new(style: PlaceholderStyle, tag: Option<&UnknownMetaTag>, context: Option<&str>) -> Self {
+        Self {
+            style,
+            explicit: tag.map(placeholders_attr).unwrap_or_default(),
+            names: vec![],
+            plural: tag.and_then(plural_attr),
+            context: tag
+                .and_then(context_attr)
+                .or_else(|| context.map(|x| x.to_string())),
+        }
+    }
+
+    /// The names of the message of the node at `index`, given by the `<!I18N>` tag right before it.
+    ///
+    /// `context` is the message context inherited from the elements, used if the tag does not give one.
+    pub(crate) fn annotated(
+        style: PlaceholderStyle,
+        context: Option<&str>,
+        node_list: &[Node],
+        index: usize,
+    ) -> Self {
+        Self::new(style, annotation(node_list, index), context)
+    }
+
+    /// The names of a translate-children group, given by its `<!I18N translate-children>` tag.
+    pub(crate) fn group(
+        style: PlaceholderStyle,
+        context: Option<&str>,
+        node_list: &[Node],
+    ) -> Self {
+        let tag = node_list.iter().find_map(|node| match node {
+            Node::UnknownMetaTag(tag) if has_i18n_translate_children(tag) => Some(tag),
+            _ => None,
+        });
+        Self::new(style, tag, context)
+    }
+
+    /// The placeholder which selects the plural form, if the message has plural forms.
+    pub(crate) fn plural(&self) -> Option<&str> {
+        self.plural.as_deref()
+    }
+
+    /// The message context of the message.
+    pub(crate) fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    fn allocate(&mut self, derived: Option<String>, shareable: bool) -> (String, bool) {
+        let index = self.names.len();
+        if let Some(name) = self.explicit.get(index).filter(|x| !x.is_empty()) {
+            let name = name.clone();
+            self.names.push((name.clone(), false));
+            return (name, true);
+        }
+        let base = match (self.style, derived) {
+            (PlaceholderStyle::Named, Some(derived)) => derived,
+            _ => letter_name(index),
+        };
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while self.names.iter().any(|x| x.0 == name) {
+            name = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+        self.names.push((name.clone(), shareable));
+        (name, true)
+    }
+
+    /// Name the placeholder of a data binding, returning the name and whether it is a new placeholder.
+    pub(crate) fn expression(&mut self, expr: &Expression) -> (String, bool) {
+        let path = expression_path(expr);
+        if let (PlaceholderStyle::Named, Some(path)) = (self.style, &path) {
+            if self.names.iter().any(|x| x.1 && x.0 == *path) {
+                return (path.clone(), false);
+            }
+        }
+        self.allocate(path, true)
+    }
+
+    /// Name the placeholder of a node in a translate-children group.
+    pub(crate) fn node(&mut self, tag_name: Option<&str>) -> String {
+        self.allocate(tag_name.map(|x| x.to_string()), false).0
+    }
+}
+
+/// Whether an expression is a string concatenation, i.e. a `+` chain with a literal or a text part in it.
+///
+/// The sides of `a + b + "x"` are evaluated as `(a + b) + "x"`, so `a + b` is not a concatenation itself.
+pub(crate) fn is_concatenation(expr: &Expression) -> bool {
+    match expr {
+        Expression::LitStr { .. } | Expression::ToStringWithoutUndefined { .. } => true,
+        Expression::Plus { left, right, .. } => is_concatenation(left) || is_concatenation(right),
+        _ => false,
+    }
+}
+
+/// Split a string expression into the literal parts and the placeholders,
+/// collecting the expression of each new placeholder into `args`.
+///
+/// The operands of a string concatenation which are not literals become placeholders,
+/// while other expressions have no message at all.
+pub(crate) fn split_expression(
+    expr: &Expression,
+    expr_vec: &mut Vec<String>,
+    names: &mut PlaceholderNames,
+    args: &mut Vec<(String, Box<Expression>)>,
+) {
+    if is_concatenation(expr) {
+        split_operand(expr, expr_vec, names, args);
+    }
+}
+
+fn split_operand(
+    expr: &Expression,
+    expr_vec: &mut Vec<String>,
+    names: &mut PlaceholderNames,
+    args: &mut Vec<(String, Box<Expression>)>,
+) {
+    let value = match expr {
+        Expression::LitStr { value, .. } => {
+            expr_vec.push(value.to_string());
+            return;
+        }
+        Expression::Plus { left, right, .. } if is_concatenation(expr) => {
+            split_operand(left, expr_vec, names, args);
+            split_operand(right, expr_vec, names, args);
+            return;
+        }
+        Expression::ToStringWithoutUndefined { value, .. } => value,
+        _ => expr,
+    };
+    let (name, is_new) = names.expression(value);
+    expr_vec.push(format!("{{{{{}}}}}", name));
+    if is_new {
+        args.push((name, Box::new(value.clone())));
+    }
+}
+
+/// The msgid of a dynamic value and the expressions of its placeholders.
+pub(crate) fn dynamic_message(
+    expression: &Expression,
+    mut names: PlaceholderNames,
+) -> (String, Vec<(String, Box<Expression>)>) {
+    let mut expr_vec = vec![];
+    let mut args = vec![];
+    split_expression(expression, &mut expr_vec, &mut names, &mut args);
+    (expr_vec.join(""), args)
+}
+
+/// The name of a placeholder like `{{name}}` in a translation.
+pub(crate) fn placeholder_name(item: &str) -> Option<&str> {
+    let name = item.strip_prefix("{{")?.strip_suffix("}}")?.trim();
+    Some(name)
+}
+
+/// The names listed in the `placeholders` attribute of an `<!I18N>` tag.
+fn placeholders_attr(tag: &UnknownMetaTag) -> Vec<String> {
+    match get_i18n_attr_value(tag, "placeholders") {
+        Some(Some(Value::Static { value, .. })) => {
+            value.split(',').map(|x| x.trim().to_string()).collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// The placeholder name in the `plural` attribute of an `<!I18N>` tag.
+fn plural_attr(tag: &UnknownMetaTag) -> Option<String> {
+    match get_i18n_attr_value(tag, "plural") {
+        Some(Some(Value::Static { value, .. })) => {
+            let value = value.trim();
+            Some(placeholder_name(value).unwrap_or(value).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The message context in the `context` attribute of an `<!I18N>` tag.
+fn context_attr(tag: &UnknownMetaTag) -> Option<String> {
+    match get_i18n_attr_value(tag, "context") {
+        Some(Some(Value::Static { value, .. })) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// The lines of a translator note, without the empty ones.
+fn note_lines(note: &str) -> impl Iterator<Item = String> + '_ {
+    note.lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+}
+
+/// The translator note in the `note` attribute of an `<!I18N>` tag.
+pub(crate) fn note_attr(tag: &UnknownMetaTag) -> Vec<String> {
+    match get_i18n_attr_value(tag, "note") {
+        Some(Some(Value::Static { value, .. })) => note_lines(value).collect(),
+        _ => vec![],
+    }
+}
+
+/// The translator note in a `<!-- i18n: ... -->` comment.
+fn comment_note(comment: &Comment) -> Option<&str> {
+    comment
+        .content
+        .trim()
+        .trim_end_matches("--")
+        .trim()
+        .strip_prefix("i18n:")
+}
+
+/// The translator notes of the node at `index`, given right before it
+/// by `<!-- i18n: ... -->` comments or the `note` attribute of the `<!I18N>` tag, in source order.
+pub(crate) fn translator_notes(node_list: &[Node], index: usize) -> Vec<String> {
+    let mut ret = vec![];
+    for node in node_list[..index].iter().rev() {
+        let notes: Vec<String> = match node {
+            Node::Text(Value::Static { value, .. }) if value.trim().is_empty() => continue,
+            Node::Comment(comment) => match comment_note(comment) {
+                Some(note) => note_lines(note).collect(),
+                None => break,
+            },
+            Node::UnknownMetaTag(tag) if is_i18n_tag(tag) && !has_i18n_translate_children(tag) => {
+                note_attr(tag)
+            }
+            _ => break,
+        };
+        ret.extend(notes.into_iter().rev());
+    }
+    ret.reverse();
+    ret
+}
+
+/// The attribute of an element with the name, if the node is a normal element.
+fn element_attribute<'a>(node: &'a Node, name: &str) -> Option<&'a NormalAttribute> {
+    let Node::Element(element) = node else {
+        return None;
+    };
+    let ElementKind::Normal { attributes, .. } = &element.kind else {
+        return None;
+    };
+    attributes.iter().find(|x| x.name.name == name)
+}
+
+/// The message context set by the `i18n-context` attribute of an element.
+pub(crate) fn element_context(node: &Node) -> Option<String> {
+    element_attribute(node, CONTEXT_ATTR).and_then(|x| match &x.value {
+        Some(Value::Static { value, .. }) => Some(value.to_string()),
+        _ => None,
+    })
+}
+
+/// Report the `i18n-context` attributes with data bindings, which cannot be used as message contexts and are ignored.
+pub(crate) fn check_element_contexts(
+    path: &str,
+    node_list: &[Node],
+    warnings: &mut Vec<Diagnostic>,
+) {
+    fn rec(path: &str, node: &Node, warnings: &mut Vec<Diagnostic>) {
+        let Node::Element(element) = node else {
+            return;
+        };
+        if let Some(attr) = element_attribute(node, CONTEXT_ATTR) {
+            if let Some(Value::Dynamic { .. }) = attr.value {
+                warnings.push(Diagnostic::new(
+                    DiagnosticKind::TemplateParseWarning,
+                    path,
+                    attr.name.location.clone(),
+                    format!(
+                        "The {} attribute should be a static string, so the data binding is ignored",
+                        CONTEXT_ATTR,
+                    ),
+                ));
+            }
+        }
+        for child in element.iter_children() {
+            rec(path, child, warnings);
+        }
+    }
+    for node in node_list {
+        rec(path, node, warnings);
+    }
+}
+
+/// Whether the node at `index` is excluded from the translation,
+/// by `<!I18N ignore>` right before it or the `i18n-ignore` attribute of the element.
+///
+/// The descendants of an excluded element are excluded as well.
+pub(crate) fn is_ignored(node_list: &[Node], index: usize) -> bool {
+    element_attribute(&node_list[index], IGNORE_ATTR).is_some()
+        || annotation(node_list, index)
+            .is_some_and(|tag| get_i18n_attr_value(tag, "ignore").is_some())
+}
+
+/// The `<!I18N>` tag right before the node at `index`, which annotates its message.
+///
+/// Whitespace-only text nodes and `<!-- i18n: ... -->` comments between them are ignored.
+fn annotation(node_list: &[Node], index: usize) -> Option<&UnknownMetaTag> {
+    for node in node_list[..index].iter().rev() {
+        match node {
+            Node::Text(Value::Static { value, .. }) if value.trim().is_empty() => continue,
+            Node::Comment(comment) if comment_note(comment).is_some() => continue,
+            Node::UnknownMetaTag(tag) if is_i18n_tag(tag) && !has_i18n_translate_children(tag) => {
+                return Some(tag);
+            }
+            _ => break,
+        }
+    }
+    None
+}
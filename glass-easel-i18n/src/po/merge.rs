@@ -0,0 +1,144 @@
+use regex::Regex;
+
+use super::{PoCatalog, PoEntry};
+
+/// The text of a msgid with placeholder names and whitespace ignored.
+///
+/// Two msgids with the same skeleton differ only in placeholder names
+/// (e.g. the letters shifted by an added translate-children element)
+/// or in whitespace, so the old translation is a good fuzzy guess.
+fn skeleton(regex: &Regex, msgid: &str) -> String {
+    let replaced = regex.replace_all(msgid, "{{}}");
+    replaced.split_whitespace().collect()
+}
+
+/// Rename the placeholders in `msgstr` from the ones in `old_msgid` to the ones in `new_msgid` by position.
+fn remap_placeholders(regex: &Regex, msgstr: &str, old_msgid: &str, new_msgid: &str) -> String {
+    let old: Vec<&str> = regex.find_iter(old_msgid).map(|x| x.as_str()).collect();
+    let new: Vec<&str> = regex.find_iter(new_msgid).map(|x| x.as_str()).collect();
+    regex
+        .replace_all(msgstr, |caps: &regex::Captures| {
+            let placeholder = &caps[0];
+            match old.iter().position(|x| *x == placeholder) {
+                Some(i) if i < new.len() => new[i].to_string(),
+                _ => placeholder.to_string(),
+            }
+        })
+        .to_string()
+}
+
+fn has_translation(entry: &PoEntry) -> bool {
+    entry.msgstr.iter().any(|x| !x.is_empty())
+}
+
+/// Merge freshly extracted terms into an existing locale catalog, like `msgmerge`.
+///
+/// * Entries in `existing` with the same msgctxt and msgid keep their translations.
+/// * An unmatched term takes the translation of an unmatched existing entry
+///   which differs only in placeholder names or whitespace, marked as `fuzzy`.
+/// * New terms are added with an empty msgstr.
+/// * Entries that no longer exist in `terms` are kept as obsolete (`#~`).
+///
+/// The header of `existing` is kept except the `POT-Creation-Date` of `terms`,
+/// and comments and references come from `terms`.
+pub fn merge_catalog(terms: &PoCatalog, existing: &PoCatalog) -> PoCatalog {
+    let regex = Regex::new(r"\{\{.*?\}\}").unwrap();
+    let mut used = vec![false; existing.entries.len()];
+    let find_exact = |entry: &PoEntry, used: &[bool]| {
+        existing
+            .entries
+            .iter()
+            .enumerate()
+            .position(|(i, x)| !used[i] && x.msgctxt == entry.msgctxt && x.msgid == entry.msgid)
+    };
+
+    // find the exact matches first so that fuzzy matching never steals them
+    let exact: Vec<Option<usize>> = terms
+        .entries
+        .iter()
+        .map(|entry| {
+            let found = find_exact(entry, &used);
+            if let Some(i) = found {
+                used[i] = true;
+            }
+            found
+        })
+        .collect();
+
+    // plural entries have as many forms as the language of the existing catalog
+    let msgstr_count = |entry: &PoEntry| match (&entry.msgid_plural, existing.nplurals()) {
+        (Some(_), Some(n)) if n > 0 => n,
+        _ => entry.msgstr.len().max(1),
+    };
+
+    let mut entries = vec![];
+    for (entry, exact) in terms.entries.iter().zip(exact) {
+        let mut new_entry = PoEntry {
+            translator_comments: vec![],
+            extracted_comments: entry.extracted_comments.clone(),
+            references: entry.references.clone(),
+            flags: entry.flags.clone(),
+            msgctxt: entry.msgctxt.clone(),
+            msgid: entry.msgid.clone(),
+            msgid_plural: entry.msgid_plural.clone(),
+            msgstr: vec![String::new(); msgstr_count(entry)],
+            obsolete: false,
+        };
+        let add_flag = |new_entry: &mut PoEntry, flag: &str| {
+            if !new_entry.has_flag(flag) {
+                new_entry.flags.push(flag.to_string());
+            }
+        };
+        if let Some(i) = exact {
+            let old = &existing.entries[i];
+            new_entry.translator_comments = old.translator_comments.clone();
+            new_entry.msgstr = old.msgstr.clone();
+            if old.is_fuzzy() {
+                add_flag(&mut new_entry, "fuzzy");
+            }
+        } else {
+            let entry_skeleton = skeleton(&regex, &entry.msgid);
+            let fuzzy = existing.entries.iter().enumerate().position(|(i, x)| {
+                !used[i]
+                    && x.msgctxt == entry.msgctxt
+                    && has_translation(x)
+                    && skeleton(&regex, &x.msgid) == entry_skeleton
+            });
+            if let Some(i) = fuzzy {
+                used[i] = true;
+                let old = &existing.entries[i];
+                new_entry.translator_comments = old.translator_comments.clone();
+                new_entry.msgstr = old
+                    .msgstr
+                    .iter()
+                    .map(|x| remap_placeholders(&regex, x, &old.msgid, &entry.msgid))
+                    .collect();
+                add_flag(&mut new_entry, "fuzzy");
+            }
+        }
+        entries.push(new_entry);
+    }
+
+    // keep the entries that are no longer used
+    for (i, old) in existing.entries.iter().enumerate() {
+        if used[i] {
+            continue;
+        }
+        entries.push(PoEntry {
+            references: vec![],
+            obsolete: true,
+            ..old.clone()
+        });
+    }
+
+    let mut catalog = PoCatalog {
+        header_comments: existing.header_comments.clone(),
+        header_flags: existing.header_flags.clone(),
+        header: existing.header.clone(),
+        entries,
+    };
+    if let Some(date) = terms.header_value("POT-Creation-Date") {
+        catalog.set_header_value("POT-Creation-Date", date);
+    }
+    catalog
+}
@@ -0,0 +1,180 @@
+mod merge;
+mod parse;
+mod write;
+
+pub use merge::merge_catalog;
+pub use parse::parse_po;
+pub use write::write_po;
+
+/// The `Plural-Forms` value used in template (POT) files.
+const TEMPLATE_PLURAL_FORMS: &str = "nplurals=INTEGER; plural=EXPRESSION;";
+
+/// The `Project-Id-Version` value used in new catalogs, the same as the one of `xgettext`.
+const PROJECT_ID_VERSION: &str = "PACKAGE VERSION";
+
+/// The current UTC time in the format of `POT-Creation-Date`.
+#[cfg(not(target_arch = "wasm32"))]
+fn creation_date() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+    // convert the days since epoch into a civil date
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}+0000",
+        year,
+        month,
+        day,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+    )
+}
+
+/// The clock is not available in WebAssembly, so the placeholder of gettext is used.
+#[cfg(target_arch = "wasm32")]
+fn creation_date() -> String {
+    "YEAR-MO-DA HO:MI+ZONE".to_string()
+}
+
+/// A single message of a gettext PO file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoEntry {
+    /// Comments written by translators (`# ...`).
+    pub translator_comments: Vec<String>,
+    /// Comments extracted from the source code (`#. ...`).
+    pub extracted_comments: Vec<String>,
+    /// Source references (`#: ...`).
+    pub references: Vec<String>,
+    /// Flags such as `fuzzy` (`#, ...`).
+    pub flags: Vec<String>,
+    pub msgctxt: Option<String>,
+    pub msgid: String,
+    pub msgid_plural: Option<String>,
+    /// The translations, one for each plural form (or a single one for non-plural messages).
+    pub msgstr: Vec<String>,
+    /// Whether the entry is commented out with `#~`.
+    pub obsolete: bool,
+}
+
+impl PoEntry {
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|x| x == flag)
+    }
+
+    pub fn is_fuzzy(&self) -> bool {
+        self.has_flag("fuzzy")
+    }
+
+    /// Get the translation of the entry if it is usable for compilation.
+    ///
+    /// Obsolete entries, fuzzy entries and empty translations are ignored.
+    pub fn translation(&self) -> Option<&str> {
+        if self.obsolete || self.is_fuzzy() {
+            return None;
+        }
+        self.msgstr
+            .first()
+            .map(|x| x.as_str())
+            .filter(|x| !x.is_empty())
+    }
+}
+
+/// A gettext PO (or POT) file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoCatalog {
+    /// Comments attached to the header entry.
+    pub header_comments: Vec<String>,
+    /// Flags of the header entry, i.e. `fuzzy` in template (POT) catalogs.
+    pub header_flags: Vec<String>,
+    /// Fields of the header entry, e.g. `Language` or `Plural-Forms`.
+    pub header: Vec<(String, String)>,
+    pub entries: Vec<PoEntry>,
+}
+
+impl PoCatalog {
+    /// Create an empty catalog with the standard header fields.
+    ///
+    /// A `None` language creates a template (POT) catalog,
+    /// which has a `POT-Creation-Date` and a `fuzzy` header like the ones generated by `xgettext`.
+    pub fn with_header(language: Option<&str>, plural_forms: Option<&str>) -> Self {
+        let mut header = vec![(
+            "Project-Id-Version".to_string(),
+            PROJECT_ID_VERSION.to_string(),
+        )];
+        if language.is_none() {
+            header.push(("POT-Creation-Date".to_string(), creation_date()));
+        }
+        header.extend([
+            ("Language".to_string(), language.unwrap_or("").to_string()),
+            ("MIME-Version".to_string(), "1.0".to_string()),
+            (
+                "Content-Type".to_string(),
+                "text/plain; charset=UTF-8".to_string(),
+            ),
+            ("Content-Transfer-Encoding".to_string(), "8bit".to_string()),
+        ]);
+        let plural_forms = match language {
+            None => Some(plural_forms.unwrap_or(TEMPLATE_PLURAL_FORMS)),
+            Some(_) => plural_forms,
+        };
+        if let Some(plural_forms) = plural_forms {
+            header.push(("Plural-Forms".to_string(), plural_forms.to_string()));
+        }
+        let header_flags = match language {
+            None => vec!["fuzzy".to_string()],
+            Some(_) => vec![],
+        };
+        Self {
+            header_comments: vec![],
+            header_flags,
+            header,
+            entries: vec![],
+        }
+    }
+
+    /// Set a header field (case-insensitive), appending it if not exists.
+    pub fn set_header_value(&mut self, name: &str, value: &str) {
+        match self
+            .header
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.header.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Get a header field (case-insensitive).
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The number of plural forms given by the `Plural-Forms` header.
+    pub fn nplurals(&self) -> Option<usize> {
+        let plural_forms = self.header_value("Plural-Forms")?;
+        let (_, rest) = plural_forms.split_once("nplurals=")?;
+        rest.split(';').next()?.trim().parse().ok()
+    }
+}
+
+/// The key used for messages with a context in translation maps.
+///
+/// This is the same convention as the one used by gettext MO files.
+pub fn msg_key(msgctxt: Option<&str>, msgid: &str) -> String {
+    match msgctxt {
+        Some(ctxt) => format!("{}\u{4}{}", ctxt, msgid),
+        None => msgid.to_string(),
+    }
+}
@@ -0,0 +1,309 @@
+use glass_easel_template_compiler::parse::Position;
+
+use super::{PoCatalog, PoEntry};
+use crate::{Diagnostic, DiagnosticKind};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Msgctxt,
+    Msgid,
+    MsgidPlural,
+    Msgstr(usize),
+}
+
+struct EntryBuilder {
+    entry: PoEntry,
+    last_field: Option<Field>,
+    has_msgid: bool,
+    has_msgstr: bool,
+    start_line: usize,
+}
+
+impl EntryBuilder {
+    fn new(start_line: usize) -> Self {
+        Self {
+            entry: PoEntry::default(),
+            last_field: None,
+            has_msgid: false,
+            has_msgstr: false,
+            start_line,
+        }
+    }
+
+    /// Take the current entry and start a new one.
+    fn take(&mut self, start_line: usize) -> Self {
+        std::mem::replace(self, Self::new(start_line))
+    }
+
+    fn has_keyword(&self) -> bool {
+        self.last_field.is_some()
+    }
+
+    fn field_mut(&mut self, field: Field) -> &mut String {
+        match field {
+            Field::Msgctxt => self.entry.msgctxt.get_or_insert_with(String::new),
+            Field::Msgid => &mut self.entry.msgid,
+            Field::MsgidPlural => self.entry.msgid_plural.get_or_insert_with(String::new),
+            Field::Msgstr(index) => {
+                if self.entry.msgstr.len() <= index {
+                    self.entry.msgstr.resize(index + 1, String::new());
+                }
+                &mut self.entry.msgstr[index]
+            }
+        }
+    }
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut ret = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        let Some(c) = chars.next() else {
+            return Err("unexpected end of string after `\\`".into());
+        };
+        match c {
+            'n' => ret.push('\n'),
+            't' => ret.push('\t'),
+            'r' => ret.push('\r'),
+            'a' => ret.push('\u{7}'),
+            'b' => ret.push('\u{8}'),
+            'f' => ret.push('\u{c}'),
+            'v' => ret.push('\u{b}'),
+            '\\' | '"' | '\'' | '?' => ret.push(c),
+            '0'..='7' => {
+                let mut code = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|x| x.to_digit(8)) {
+                        Some(d) => {
+                            code = code * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                ret.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            'x' => {
+                let mut code: u32 = 0;
+                let mut len = 0;
+                while let Some(d) = chars.peek().and_then(|x| x.to_digit(16)) {
+                    code = code.saturating_mul(16).saturating_add(d);
+                    len += 1;
+                    chars.next();
+                }
+                if len == 0 {
+                    return Err("illegal escape sequence `\\x`".into());
+                }
+                ret.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            _ => return Err(format!("illegal escape sequence `\\{}`", c)),
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_quoted(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return Err("expected a quoted string".into());
+    }
+    let inner = &s[1..s.len() - 1];
+    // an unescaped quote inside the string is not allowed
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            '\\' => escaped = !escaped,
+            '"' if !escaped => return Err("unexpected `\"` inside string".into()),
+            _ => escaped = false,
+        }
+    }
+    if escaped {
+        return Err("unexpected end of string after `\\`".into());
+    }
+    unescape(inner)
+}
+
+/// The maximum number of the plural forms of a message, far more than any language has.
+const MAX_PLURAL_FORMS: usize = 16;
+
+fn parse_keyword(line: &str) -> Result<(Field, &str), String> {
+    let keyword_end = line
+        .find(|c: char| c.is_whitespace() || c == '"')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(keyword_end);
+    let field = match keyword {
+        "msgctxt" => Field::Msgctxt,
+        "msgid" => Field::Msgid,
+        "msgid_plural" => Field::MsgidPlural,
+        "msgstr" => Field::Msgstr(0),
+        _ => {
+            let index = keyword
+                .strip_prefix("msgstr[")
+                .and_then(|x| x.strip_suffix(']'))
+                .and_then(|x| x.parse::<usize>().ok());
+            match index {
+                Some(index) if index < MAX_PLURAL_FORMS => Field::Msgstr(index),
+                Some(_) => {
+                    return Err(format!(
+                        "too many plural forms in `{}`, at most {} are allowed",
+                        keyword, MAX_PLURAL_FORMS,
+                    ))
+                }
+                None => return Err(format!("unknown keyword `{}`", keyword)),
+            }
+        }
+    };
+    Ok((field, rest))
+}
+
+fn parse_header(msgstr: &str) -> Vec<(String, String)> {
+    msgstr
+        .lines()
+        .filter_map(|line| {
+            let (k, v) = line.split_once(':')?;
+            Some((k.trim().to_string(), v.trim().to_string()))
+        })
+        .collect()
+}
+
+fn line_location(source: &str, line_index: usize) -> std::ops::Range<Position> {
+    let line = source.lines().nth(line_index).unwrap_or("");
+    let start = Position {
+        line: line_index as u32,
+        utf16_col: 0,
+    };
+    let end = Position {
+        line: line_index as u32,
+        utf16_col: line.encode_utf16().count() as u32,
+    };
+    start..end
+}
+
+/// Parse a gettext PO (or POT) file.
+pub fn parse_po(path: &str, source: &str) -> Result<PoCatalog, Diagnostic> {
+    // editors on Windows may save the file with a byte order mark
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let mut catalog = PoCatalog::default();
+    let mut header_found = false;
+    let mut builder = EntryBuilder::new(0);
+
+    let err_at = |line_index: usize, message: String| {
+        Diagnostic::new(
+            DiagnosticKind::TranslationFileSyntaxError,
+            path,
+            line_location(source, line_index),
+            message,
+        )
+    };
+    let mut finish = |builder: EntryBuilder| -> Result<(), Diagnostic> {
+        if !builder.has_keyword() {
+            return Ok(());
+        }
+        if !builder.has_msgid {
+            return Err(err_at(builder.start_line, "missing msgid".into()));
+        }
+        if !builder.has_msgstr {
+            return Err(err_at(builder.start_line, "missing msgstr".into()));
+        }
+        let entry = builder.entry;
+        if !header_found && entry.msgid.is_empty() && entry.msgctxt.is_none() && !entry.obsolete {
+            header_found = true;
+            catalog.header = parse_header(entry.msgstr.first().map(|x| x.as_str()).unwrap_or(""));
+            catalog.header_comments = entry.translator_comments;
+            catalog.header_flags = entry.flags;
+        } else {
+            catalog.entries.push(entry);
+        }
+        Ok(())
+    };
+
+    for (line_index, line) in source.lines().enumerate() {
+        let err = |message: String| err_at(line_index, message);
+        let mut line = line.trim();
+        if line.is_empty() {
+            finish(builder.take(line_index))?;
+            continue;
+        }
+
+        // obsolete entries are commented out by `#~` but otherwise parsed as usual
+        let mut obsolete = false;
+        if let Some(rest) = line.strip_prefix("#~") {
+            if rest.starts_with('|') {
+                continue;
+            }
+            obsolete = true;
+            line = rest.trim_start();
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            if builder.has_keyword() {
+                finish(builder.take(line_index))?;
+            }
+            let entry = &mut builder.entry;
+            if let Some(flags) = comment.strip_prefix(',') {
+                entry.flags.extend(
+                    flags
+                        .split(',')
+                        .map(|x| x.trim())
+                        .filter(|x| !x.is_empty())
+                        .map(|x| x.to_string()),
+                );
+            } else if let Some(x) = comment.strip_prefix('.') {
+                entry.extracted_comments.push(x.trim().to_string());
+            } else if let Some(x) = comment.strip_prefix(':') {
+                entry
+                    .references
+                    .extend(x.split_whitespace().map(|x| x.to_string()));
+            } else if comment.starts_with('|') {
+                // previous strings are only meaningful for fuzzy matching
+            } else {
+                let x = comment.strip_prefix(' ').unwrap_or(comment);
+                entry.translator_comments.push(x.trim_end().to_string());
+            }
+            continue;
+        }
+
+        if line.starts_with('"') {
+            let Some(field) = builder.last_field else {
+                return Err(err("string without a keyword".into()));
+            };
+            let s = parse_quoted(line).map_err(err)?;
+            builder.field_mut(field).push_str(&s);
+            continue;
+        }
+
+        let (field, rest) = parse_keyword(line).map_err(err)?;
+        let starts_new_entry = match field {
+            Field::Msgctxt => builder.has_keyword(),
+            Field::Msgid => builder.has_msgid,
+            _ => false,
+        };
+        if starts_new_entry {
+            finish(builder.take(line_index))?;
+        }
+        if !builder.has_keyword() {
+            builder.start_line = line_index;
+        }
+        match field {
+            Field::Msgid => builder.has_msgid = true,
+            Field::Msgstr(_) => {
+                if !builder.has_msgid {
+                    return Err(err("msgstr before msgid".into()));
+                }
+                builder.has_msgstr = true;
+            }
+            _ => {}
+        }
+        builder.entry.obsolete |= obsolete;
+        let s = parse_quoted(rest).map_err(err)?;
+        *builder.field_mut(field) = s;
+        builder.last_field = Some(field);
+    }
+    finish(builder)?;
+
+    Ok(catalog)
+}
@@ -0,0 +1,143 @@
+use std::fmt::Write;
+
+use super::{PoCatalog, PoEntry};
+
+/// The max line width of the output, the same as the default of gettext tools.
+const LINE_WIDTH: usize = 79;
+
+fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => ret.push_str("\\\\"),
+            '"' => ret.push_str("\\\""),
+            '\n' => ret.push_str("\\n"),
+            '\t' => ret.push_str("\\t"),
+            '\r' => ret.push_str("\\r"),
+            '\u{7}' => ret.push_str("\\a"),
+            '\u{8}' => ret.push_str("\\b"),
+            '\u{c}' => ret.push_str("\\f"),
+            '\u{b}' => ret.push_str("\\v"),
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Split an escaped string into lines that fit the width.
+///
+/// Lines are always broken after `\n` and otherwise only after spaces.
+fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut ret = vec![];
+    for segment in s.split_inclusive('\n') {
+        let mut line = String::new();
+        let mut line_len = 0;
+        for word in escape(segment).split_inclusive(' ') {
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + word_len > width {
+                ret.push(std::mem::take(&mut line));
+                line_len = 0;
+            }
+            line.push_str(word);
+            line_len += word_len;
+        }
+        ret.push(line);
+    }
+    if ret.is_empty() {
+        ret.push(String::new());
+    }
+    ret
+}
+
+fn write_string(out: &mut String, prefix: &str, keyword: &str, s: &str, multiline: bool) {
+    let first_line_width = LINE_WIDTH - prefix.len() - keyword.len() - 3;
+    let lines = wrap(s, first_line_width);
+    if lines.len() == 1 && !multiline {
+        writeln!(out, "{}{} \"{}\"", prefix, keyword, lines[0]).unwrap();
+        return;
+    }
+    writeln!(out, "{}{} \"\"", prefix, keyword).unwrap();
+    for line in wrap(s, LINE_WIDTH - prefix.len() - 2) {
+        writeln!(out, "{}\"{}\"", prefix, line).unwrap();
+    }
+}
+
+fn write_references(out: &mut String, references: &[String]) {
+    let mut line = String::new();
+    for reference in references {
+        if !line.is_empty() && line.len() + reference.len() + 1 > LINE_WIDTH - 2 {
+            writeln!(out, "#:{}", line).unwrap();
+            line.clear();
+        }
+        line.push(' ');
+        line.push_str(reference);
+    }
+    if !line.is_empty() {
+        writeln!(out, "#:{}", line).unwrap();
+    }
+}
+
+fn write_entry(out: &mut String, entry: &PoEntry, is_header: bool) {
+    for comment in &entry.translator_comments {
+        if comment.is_empty() {
+            writeln!(out, "#").unwrap();
+        } else {
+            writeln!(out, "# {}", comment).unwrap();
+        }
+    }
+    for comment in &entry.extracted_comments {
+        writeln!(out, "#. {}", comment).unwrap();
+    }
+    write_references(out, &entry.references);
+    if !entry.flags.is_empty() {
+        writeln!(out, "#, {}", entry.flags.join(", ")).unwrap();
+    }
+    let prefix = if entry.obsolete { "#~ " } else { "" };
+    if let Some(msgctxt) = &entry.msgctxt {
+        write_string(out, prefix, "msgctxt", msgctxt, false);
+    }
+    write_string(out, prefix, "msgid", &entry.msgid, false);
+    match &entry.msgid_plural {
+        Some(msgid_plural) => {
+            write_string(out, prefix, "msgid_plural", msgid_plural, false);
+            let count = entry.msgstr.len().max(2);
+            for i in 0..count {
+                let msgstr = entry.msgstr.get(i).map(|x| x.as_str()).unwrap_or("");
+                write_string(out, prefix, &format!("msgstr[{}]", i), msgstr, false);
+            }
+        }
+        None => {
+            let msgstr = entry.msgstr.first().map(|x| x.as_str()).unwrap_or("");
+            write_string(out, prefix, "msgstr", msgstr, is_header);
+        }
+    }
+}
+
+/// Write a gettext PO (or POT) file.
+pub fn write_po(catalog: &PoCatalog) -> String {
+    let mut out = String::new();
+    if !catalog.header.is_empty()
+        || !catalog.header_comments.is_empty()
+        || !catalog.header_flags.is_empty()
+    {
+        let header_str: String = catalog
+            .header
+            .iter()
+            .map(|(k, v)| format!("{}: {}\n", k, v))
+            .collect();
+        let header = PoEntry {
+            translator_comments: catalog.header_comments.clone(),
+            flags: catalog.header_flags.clone(),
+            msgstr: vec![header_str],
+            ..Default::default()
+        };
+        write_entry(&mut out, &header, true);
+    }
+    for entry in &catalog.entries {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        write_entry(&mut out, entry, false);
+    }
+    out
+}
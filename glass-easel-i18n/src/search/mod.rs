@@ -1,181 +1,241 @@
-use super::contains_i18n_translate_children;
-use glass_easel_template_compiler::parse::{
-    expr::Expression,
-    parse,
-    tag::{ElementKind, Node, NormalAttribute, Value},
+use crate::{
+    attribute::parse_attribute_rules, diagnostic::collect_parse_warnings, msg_key,
+    placeholder::check_element_contexts, visit_group, visit_units, AttributeRule, Diagnostic,
+    ExtractOptions, LiteralPolicy, PlaceholderStyle, PoCatalog, PoEntry, TermOrigin,
+    TranslatableUnit, UnitMessage, UnitTarget, WhitespacePolicy,
 };
+use glass_easel_template_compiler::parse::{parse, Position};
+use std::ops::Range;
 
-pub struct UntranslatedTerms {
-    pub output: Vec<String>,
+/// A single occurrence of a term in the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermLocation {
+    pub location: Range<Position>,
+    pub origin: TermOrigin,
+    /// The tag name of the closest enclosing element.
+    pub tag_name: Option<String>,
+    /// The name of the sub-template (`<template name="...">`) if the term is inside one.
+    pub sub_template: Option<String>,
 }
 
-pub fn search(
-    path: &str,
-    source: &str,
-    included_attributes: &[String],
-) -> Result<UntranslatedTerms, String> {
-    // parse the template
-    let (template, parse_state) = parse(path, source);
-    for warning in parse_state.warnings() {
-        if warning.prevent_success() {
-            return Err(format!("Failed to compile template: {}", warning));
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UntranslatedTerm {
+    pub term: String,
+    /// The message context (msgctxt) given by `<!I18N context="...">` or an `i18n-context` attribute.
+    pub context: Option<String>,
+    /// All occurrences of the term, in source order.
+    pub locations: Vec<TermLocation>,
+    /// Whether any occurrence is annotated with `<!I18N plural="...">`, so the term has plural forms.
+    pub plural: bool,
+    /// The notes to translators given by `<!-- i18n: ... -->` comments or `<!I18N note="...">`.
+    pub notes: Vec<String>,
+}
+
+impl UntranslatedTerm {
+    /// The key of the term in translation maps, which contains the message context if any.
+    pub fn key(&self) -> String {
+        msg_key(self.context.as_deref(), &self.term)
+    }
+
+    /// The `path:line` references of all occurrences, as used by PO files.
+    pub fn references(&self, path: &str) -> Vec<String> {
+        let mut ret: Vec<String> = vec![];
+        for x in &self.locations {
+            let reference = format!("{}:{}", path, x.location.start.line + 1);
+            if !ret.contains(&reference) {
+                ret.push(reference);
+            }
         }
+        ret
+    }
+}
+
+pub struct UntranslatedTerms {
+    /// The path of the template, which the locations refer to.
+    pub path: String,
+    pub output: Vec<UntranslatedTerm>,
+    /// Non-fatal problems found in the template.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl UntranslatedTerms {
+    /// Generate a template (POT) catalog with references to the template.
+    pub fn to_catalog(&self) -> PoCatalog {
+        terms_to_catalog([self])
     }
-    let mut output = vec![];
-    fn collect_terms(value: &Value, terms_vec: &mut Vec<String>) {
-        match value {
-            Value::Static { value, .. } => {
-                let untranslated_term = value.trim().to_string();
-                if !terms_vec.contains(&untranslated_term) {
-                    terms_vec.push(untranslated_term);
+}
+
+/// Generate a template (POT) catalog from the terms of several templates.
+///
+/// Identical terms are merged into a single entry with the references of all occurrences.
+/// Plural terms use the msgid as `msgid_plural` as well, since the source text has no plural forms itself.
+/// The notes of the terms become extracted comments (`#.`).
+pub fn terms_to_catalog<'a>(list: impl IntoIterator<Item = &'a UntranslatedTerms>) -> PoCatalog {
+    let mut catalog = PoCatalog::with_header(None, None);
+    for terms in list {
+        for term in &terms.output {
+            let references = term.references(&terms.path);
+            let entry = catalog
+                .entries
+                .iter_mut()
+                .find(|x| x.msgid == term.term && x.msgctxt == term.context);
+            let entry = match entry {
+                Some(entry) => {
+                    entry.references.extend(references);
+                    entry
                 }
-            }
-            Value::Dynamic { expression, .. } => {
-                fn split_expression(
-                    expr: &Expression,
-                    expr_vec: &mut Vec<String>,
-                    placehoder_char: &mut char,
-                ) {
-                    match expr {
-                        Expression::LitStr { value, .. } => expr_vec.push(value.to_string()),
-                        Expression::ToStringWithoutUndefined { .. } => {
-                            expr_vec.push(format!("{{{{{}}}}}", placehoder_char));
-                            *placehoder_char = ((*placehoder_char as u8) + 1) as char;
-                        }
-                        Expression::Plus { left, right, .. } => {
-                            let split = if let Expression::ToStringWithoutUndefined { .. }
-                            | Expression::LitStr { .. } = &**left
-                            {
-                                true
-                            } else if let Expression::ToStringWithoutUndefined { .. }
-                            | Expression::LitStr { .. } = &**right
-                            {
-                                true
-                            } else {
-                                false
-                            };
-                            if split {
-                                split_expression(&left, expr_vec, placehoder_char);
-                                split_expression(&right, expr_vec, placehoder_char);
-                            }
-                        }
-                        _ => {}
-                    }
+                None => {
+                    catalog.entries.push(PoEntry {
+                        references,
+                        msgctxt: term.context.clone(),
+                        msgid: term.term.clone(),
+                        msgstr: vec![String::new()],
+                        ..Default::default()
+                    });
+                    catalog.entries.last_mut().unwrap()
                 }
-                let mut expr_vec: Vec<String> = Vec::new();
-                let mut start_placeholder = 'A';
-                split_expression(&expression, &mut expr_vec, &mut start_placeholder);
-                terms_vec.push(expr_vec.join(""));
+            };
+            for note in &term.notes {
+                if !entry.extracted_comments.contains(note) {
+                    entry.extracted_comments.push(note.clone());
+                }
+            }
+            if term.plural && entry.msgid_plural.is_none() {
+                entry.msgid_plural = Some(term.term.clone());
+                entry.msgstr = vec![String::new(); 2];
             }
-            _ => {}
         }
     }
-    fn collect_attribute_terms(
-        attributes: &Vec<NormalAttribute>,
-        terms_vec: &mut Vec<String>,
-        included_attributes: &[String],
-    ) {
-        for attribute in attributes {
-            if included_attributes.contains(&attribute.name.name.to_string()) {
-                if let Some(value) = &attribute.value {
-                    collect_terms(value, terms_vec)
-                }
-            }
+    catalog
+}
+
+/// Options of the term searching.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// The rules of the attributes to search besides the text nodes, see [`AttributeRule`].
+    pub included_attributes: Vec<String>,
+    /// How the placeholders in terms are named.
+    pub placeholder_style: PlaceholderStyle,
+    /// How the whitespace of texts is normalized into terms.
+    pub whitespace: WhitespacePolicy,
+    /// Which string literals inside data bindings are searched.
+    pub literals: LiteralPolicy,
+}
+
+impl SearchOptions {
+    /// The options of finding the translatable units with the parsed attribute rules,
+    /// which finds the same units as searching when passed to [`visit_units`].
+    pub fn extract_options<'a>(&self, attributes: &'a [AttributeRule]) -> ExtractOptions<'a> {
+        ExtractOptions {
+            attributes,
+            placeholder_style: self.placeholder_style,
+            whitespace: self.whitespace,
+            literals: self.literals,
+            literals_with_messages: false,
         }
     }
-    fn collect_entire_children(
-        node_list: &Vec<Node>,
-        terms_vec: &mut Vec<String>,
-        included_attributes: &[String],
+}
+
+pub fn search(
+    path: &str,
+    source: &str,
+    included_attributes: &[String],
+) -> Result<UntranslatedTerms, Diagnostic> {
+    let options = SearchOptions {
+        included_attributes: included_attributes.to_vec(),
+        ..Default::default()
+    };
+    search_with_options(path, source, &options)
+}
+
+pub fn search_with_options(
+    path: &str,
+    source: &str,
+    options: &SearchOptions,
+) -> Result<UntranslatedTerms, Diagnostic> {
+    // parse the template
+    let (mut template, parse_state) = parse(path, source);
+    let mut warnings = collect_parse_warnings(parse_state.warnings())?;
+    check_element_contexts(path, &template.content, &mut warnings);
+    for sub_template in &template.globals.sub_templates {
+        check_element_contexts(path, &sub_template.content, &mut warnings);
+    }
+    let mut output: Vec<UntranslatedTerm> = vec![];
+
+    fn add_term(
+        message: UnitMessage,
+        location: TermLocation,
+        terms_vec: &mut Vec<UntranslatedTerm>,
     ) {
-        let mut text_vec: Vec<String> = Vec::new();
-        let mut placehoder_char = 'A';
-        for node in node_list.into_iter() {
-            match node {
-                // handle <!I18N translate-children>
-                Node::UnknownMetaTag(..) => {
-                    continue;
-                }
-                Node::Text(value) => {
-                    if let Value::Static { value, .. } = value {
-                        text_vec.push(value.trim().to_string());
+        let plural = message.plural.is_some();
+        match terms_vec
+            .iter_mut()
+            .find(|x| x.term == message.msgid && x.context == message.context)
+        {
+            Some(x) => {
+                x.locations.push(location);
+                x.plural |= plural;
+                for note in message.notes {
+                    if !x.notes.contains(&note) {
+                        x.notes.push(note);
                     }
                 }
-                Node::Element(element) => match &element.kind {
-                    ElementKind::Normal { children, .. } => {
-                        text_vec.push(format!("{{{{{}}}}}", placehoder_char));
-                        placehoder_char = ((placehoder_char as u8) + 1) as char;
-                        search_terms(children, terms_vec, included_attributes)
-                    }
-                    _ => {}
-                },
-                _ => {}
             }
+            None => terms_vec.push(UntranslatedTerm {
+                term: message.msgid,
+                context: message.context,
+                locations: vec![location],
+                plural,
+                notes: message.notes,
+            }),
         }
-        terms_vec.push(text_vec.join(""));
     }
-    fn search_terms(
-        node_list: &Vec<Node>,
-        terms_vec: &mut Vec<String>,
-        included_attributes: &[String],
-    ) {
-        for node in node_list {
-            match node {
-                Node::Element(element) => match &element.kind {
-                    ElementKind::Normal {
-                        children,
-                        attributes,
-                        ..
-                    } => {
-                        if attributes.len() != 0 {
-                            collect_attribute_terms(attributes, terms_vec, included_attributes);
-                        }
-                        if contains_i18n_translate_children(children) {
-                            collect_entire_children(children, terms_vec, included_attributes);
-                        } else {
-                            search_terms(children, terms_vec, included_attributes);
-                        }
-                    }
-                    ElementKind::If {
-                        branches,
-                        else_branch,
-                        ..
-                    } => {
-                        for branch in branches {
-                            search_terms(&branch.2, terms_vec, included_attributes)
-                        }
-                        match else_branch {
-                            Some((_, ref nodes)) => {
-                                search_terms(nodes, terms_vec, included_attributes)
-                            }
-                            _ => {}
-                        }
-                    }
-                    ElementKind::For { children, .. } => {
-                        search_terms(children, terms_vec, included_attributes)
-                    }
-                    ElementKind::Pure { children, .. } => {
-                        search_terms(children, terms_vec, included_attributes)
-                    }
-                    _ => {}
-                },
-                Node::Text(value) => {
-                    collect_terms(value, terms_vec);
-                }
-                _ => {}
-            }
+    /// Add the term of the unit, in which the terms inside a translate-children group precede the group.
+    fn visit(
+        unit: TranslatableUnit,
+        options: ExtractOptions,
+        sub_template: Option<&str>,
+        terms_vec: &mut Vec<UntranslatedTerm>,
+    ) -> bool {
+        let location = TermLocation {
+            location: unit.location,
+            origin: unit.origin,
+            tag_name: unit.tag_name,
+            sub_template: sub_template.map(|x| x.to_string()),
+        };
+        if let UnitTarget::Children(children) = unit.target {
+            let context = unit.inherited_context.as_deref();
+            visit_group(children, options, context, &mut |unit| {
+                visit(unit, options, sub_template, terms_vec)
+            });
+            add_term(unit.message, location, terms_vec);
+            return false;
         }
+        add_term(unit.message, location, terms_vec);
+        true
     }
+    let attributes = parse_attribute_rules(path, &options.included_attributes)?;
+    let extract_options = options.extract_options(&attributes);
+
     // template.content
-    search_terms(&template.content, &mut output, &included_attributes);
+    visit_units(&mut template.content, extract_options, None, &mut |unit| {
+        visit(unit, extract_options, None, &mut output)
+    });
 
     // sub_templates
-    for sub_template in &template.globals.sub_templates {
-        search_terms(&sub_template.content, &mut output, &included_attributes);
+    for sub_template in &mut template.globals.sub_templates {
+        let name = sub_template.name.name.to_string();
+        visit_units(
+            &mut sub_template.content,
+            extract_options,
+            None,
+            &mut |unit| visit(unit, extract_options, Some(&name), &mut output),
+        );
     }
 
-    // splice empty string
-    output.retain(|s| !s.trim().is_empty());
-
-    Ok(UntranslatedTerms { output })
+    Ok(UntranslatedTerms {
+        path: path.to_string(),
+        output,
+        warnings,
+    })
 }
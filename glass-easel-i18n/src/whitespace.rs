@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+/// How the whitespace of texts is normalized into msgids.
+///
+/// The outer whitespace removed from a text is kept around its translation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WhitespacePolicy {
+    /// Remove the leading and trailing whitespace.
+    #[default]
+    Trim,
+    /// Remove the leading and trailing whitespace, and collapse the inner runs of whitespace into single spaces.
+    Collapse,
+    /// Keep the text as is.
+    Preserve,
+}
+
+impl FromStr for WhitespacePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trim" => Ok(Self::Trim),
+            "collapse" => Ok(Self::Collapse),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(format!("Unknown whitespace policy: {}", s)),
+        }
+    }
+}
+
+/// A text split into its msgid and the outer whitespace around it.
+#[derive(Debug, Clone)]
+pub(crate) struct NormalizedText {
+    pub(crate) leading: String,
+    pub(crate) msgid: String,
+    pub(crate) trailing: String,
+}
+
+impl WhitespacePolicy {
+    pub(crate) fn normalize(self, text: &str) -> NormalizedText {
+        if self == Self::Preserve {
+            return NormalizedText {
+                leading: String::new(),
+                msgid: text.to_string(),
+                trailing: String::new(),
+            };
+        }
+        let trimmed = text.trim_start();
+        let leading = &text[..text.len() - trimmed.len()];
+        let trimmed = trimmed.trim_end();
+        let trailing = &text[leading.len() + trimmed.len()..];
+        let msgid = match self {
+            Self::Collapse => trimmed.split_whitespace().collect::<Vec<_>>().join(" "),
+            _ => trimmed.to_string(),
+        };
+        NormalizedText {
+            leading: leading.to_string(),
+            msgid,
+            trailing: trailing.to_string(),
+        }
+    }
+
+    /// Normalize the text of a translate-children group, joined from the texts and the placeholders of the nodes.
+    ///
+    /// With [`WhitespacePolicy::Trim`], each part is trimmed separately,
+    /// since the whitespace between the nodes is usually for indentation only.
+    pub(crate) fn normalize_parts(self, parts: &[String]) -> NormalizedText {
+        let mut ret = self.normalize(&parts.concat());
+        if self == Self::Trim {
+            ret.msgid = parts.iter().map(|x| x.trim()).collect();
+        }
+        ret
+    }
+}
@@ -0,0 +1,401 @@
+use glass_easel_i18n::{
+    is_i18n_template, merge_catalog, parse_po, search, search_with_options, terms_to_catalog,
+    write_po, DiagnosticKind, PlaceholderStyle, PoCatalog, PoEntry, SearchOptions,
+    UntranslatedTerms,
+};
+
+/// Search with the placeholders named after the bindings.
+fn search_named(path: &str, src: &str) -> UntranslatedTerms {
+    let options = SearchOptions {
+        placeholder_style: PlaceholderStyle::Named,
+        ..Default::default()
+    };
+    search_with_options(path, src, &options).unwrap()
+}
+
+#[test]
+fn basic() {
+    const SRC: &str = r#"
+msgid ""
+msgstr ""
+"Language: en-us\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+
+msgid "一些文字"
+msgstr "Some words"
+"#;
+    let res = parse_po("TEST", SRC).unwrap();
+    assert_eq!(res.header_value("language"), Some("en-us"));
+    assert_eq!(res.entries.len(), 1);
+    assert_eq!(res.entries[0].msgid, "一些文字");
+    assert_eq!(res.entries[0].msgstr, vec!["Some words".to_string()]);
+}
+
+#[test]
+fn multiline_and_escapes() {
+    const SRC: &str = r#"
+msgid ""
+"第一行\n"
+"第二行"
+msgstr "Line \"1\"\nLine\t2\\"
+"#;
+    let res = parse_po("TEST", SRC).unwrap();
+    assert_eq!(res.entries[0].msgid, "第一行\n第二行");
+    assert_eq!(res.entries[0].msgstr[0], "Line \"1\"\nLine\t2\\");
+}
+
+#[test]
+fn comments_and_flags() {
+    const SRC: &str = r#"
+# translator comment
+#. extracted comment
+#: index.wxml:3 index.wxml:5
+#, fuzzy, c-format
+msgctxt "status"
+msgid "打开"
+msgstr "Open"
+msgid "关闭"
+msgstr "Close"
+
+#~ msgid "旧的"
+#~ msgstr "Old"
+"#;
+    let res = parse_po("TEST", SRC).unwrap();
+    assert_eq!(res.entries.len(), 3);
+    let entry = &res.entries[0];
+    assert_eq!(entry.translator_comments, vec!["translator comment"]);
+    assert_eq!(entry.extracted_comments, vec!["extracted comment"]);
+    assert_eq!(entry.references, vec!["index.wxml:3", "index.wxml:5"]);
+    assert_eq!(entry.flags, vec!["fuzzy", "c-format"]);
+    assert_eq!(entry.msgctxt.as_deref(), Some("status"));
+    assert_eq!(entry.translation(), None);
+    assert_eq!(res.entries[1].translation(), Some("Close"));
+    assert!(res.entries[2].obsolete);
+}
+
+#[test]
+fn plural() {
+    const SRC: &str = r#"
+msgid "{{A}} 个文件"
+msgid_plural "{{A}} 个文件"
+msgstr[0] "{{A}} file"
+msgstr[1] "{{A}} files"
+"#;
+    let res = parse_po("TEST", SRC).unwrap();
+    assert_eq!(res.entries[0].msgid_plural.as_deref(), Some("{{A}} 个文件"));
+    assert_eq!(res.entries[0].msgstr, vec!["{{A}} file", "{{A}} files"]);
+}
+
+#[test]
+fn syntax_error() {
+    const SRC: &str = r#"
+msgid "一些文字"
+msgstr "Some words
+"#;
+    let err = parse_po("TEST", SRC).unwrap_err();
+    assert_eq!(err.kind, DiagnosticKind::TranslationFileSyntaxError);
+    assert_eq!(err.path, "TEST");
+    assert_eq!(err.location.start.line, 2);
+}
+
+#[test]
+fn plural_index_error() {
+    for index in ["999999999", "18446744073709551615"] {
+        let src = format!("msgid \"a\"\nmsgid_plural \"a\"\nmsgstr[{}] \"b\"\n", index);
+        let err = parse_po("TEST", &src).unwrap_err();
+        assert_eq!(err.kind, DiagnosticKind::TranslationFileSyntaxError);
+        assert_eq!(err.location.start.line, 2);
+    }
+}
+
+#[test]
+fn byte_order_mark() {
+    let res = parse_po(
+        "TEST",
+        "\u{feff}msgid \"一些文字\"\nmsgstr \"Some words\"\n",
+    )
+    .unwrap();
+    assert_eq!(res.entries[0].msgid, "一些文字");
+    assert_eq!(res.entries[0].translation(), Some("Some words"));
+}
+
+#[test]
+fn write_escapes_and_round_trip() {
+    let mut catalog = PoCatalog::with_header(Some("en-us"), Some("nplurals=2; plural=(n != 1);"));
+    catalog.entries.push(PoEntry {
+        references: vec!["index.wxml:3".into()],
+        msgctxt: Some("status".into()),
+        msgid: "他说 \"你好\"\n然后\\离开".into(),
+        msgstr: vec!["He said \"hello\"\nthen\\left".into()],
+        ..Default::default()
+    });
+    let out = write_po(&catalog);
+    assert!(out.contains("msgid \"\"\n\"他说 \\\"你好\\\"\\n\"\n\"然后\\\\离开\"\n"));
+    let parsed = parse_po("TEST", &out).unwrap();
+    assert_eq!(parsed, catalog);
+}
+
+#[test]
+fn write_wraps_long_lines() {
+    let mut catalog = PoCatalog::default();
+    catalog.entries.push(PoEntry {
+        msgid: "word ".repeat(30).trim().to_string(),
+        msgstr: vec![String::new()],
+        ..Default::default()
+    });
+    let out = write_po(&catalog);
+    assert!(out.starts_with("msgid \"\"\n"));
+    assert!(out.lines().all(|x| x.chars().count() <= 79));
+    assert_eq!(parse_po("TEST", &out).unwrap(), catalog);
+}
+
+#[test]
+fn search_to_pot() {
+    const SRC: &str = r#"<!I18N>
+<view>一些文字</view>
+<view>"引号"</view>
+<view>一些文字</view>"#;
+    let res = search("index.wxml", SRC, &[]).unwrap();
+    let mut catalog = res.to_catalog();
+    assert!(catalog.header_value("POT-Creation-Date").is_some());
+    catalog.set_header_value("POT-Creation-Date", "2024-01-01 00:00+0000");
+    let out = write_po(&catalog);
+    const POT: &str = r#"#, fuzzy
+msgid ""
+msgstr ""
+"Project-Id-Version: PACKAGE VERSION\n"
+"POT-Creation-Date: 2024-01-01 00:00+0000\n"
+"Language: \n"
+"MIME-Version: 1.0\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+"Content-Transfer-Encoding: 8bit\n"
+"Plural-Forms: nplurals=INTEGER; plural=EXPRESSION;\n"
+
+#: index.wxml:2 index.wxml:4
+msgid "一些文字"
+msgstr ""
+
+#: index.wxml:3
+msgid "\"引号\""
+msgstr ""
+"#;
+    assert_eq!(out, POT);
+    assert_eq!(parse_po("messages.pot", &out).unwrap(), catalog);
+}
+
+#[test]
+fn merge() {
+    const SRC: &str = r#"<!I18N>
+<view>一些文字</view>
+<view>新的文字</view>
+<div><!I18N translate-children><b>我</b>爱<i>你</i></div>"#;
+    const EXISTING: &str = r#"msgid ""
+msgstr ""
+"Language: en-us\n"
+
+# keep this
+msgid "一些文字"
+msgstr "Some words"
+
+msgid "{{A}} 爱 {{B}}"
+msgstr "{{B}} loves {{A}}"
+
+msgid "删掉的文字"
+msgstr "Removed words"
+
+msgid "未翻译的文字"
+msgstr ""
+"#;
+    let res = search("index.wxml", SRC, &[]).unwrap();
+    let existing = parse_po("en-us.po", EXISTING).unwrap();
+    let mut terms = res.to_catalog();
+    terms.set_header_value("POT-Creation-Date", "2024-01-01 00:00+0000");
+    let merged = merge_catalog(&terms, &existing);
+    const OUT: &str = r#"msgid ""
+msgstr ""
+"Language: en-us\n"
+"POT-Creation-Date: 2024-01-01 00:00+0000\n"
+
+# keep this
+#: index.wxml:2
+msgid "一些文字"
+msgstr "Some words"
+
+#: index.wxml:3
+msgid "新的文字"
+msgstr ""
+
+#: index.wxml:4
+msgid "我"
+msgstr ""
+
+#: index.wxml:4
+msgid "你"
+msgstr ""
+
+#: index.wxml:4
+#, fuzzy
+msgid "{{A}}爱{{B}}"
+msgstr "{{B}} loves {{A}}"
+
+#~ msgid "删掉的文字"
+#~ msgstr "Removed words"
+
+#~ msgid "未翻译的文字"
+#~ msgstr ""
+"#;
+    assert_eq!(write_po(&merged), OUT);
+}
+
+#[test]
+fn merge_named_placeholders() {
+    const SRC: &str = r#"<!I18N>
+<div><!I18N translate-children><b>我</b>爱<i>你</i></div>"#;
+    const EXISTING: &str = r#"msgid "{{A}} 爱 {{B}}"
+msgstr "{{B}} loves {{A}}"
+"#;
+    // the translations with letter placeholders are kept as fuzzy ones with the new names
+    let res = search_named("index.wxml", SRC);
+    let existing = parse_po("en-us.po", EXISTING).unwrap();
+    let merged = merge_catalog(&res.to_catalog(), &existing);
+    let entry = merged
+        .entries
+        .iter()
+        .find(|x| x.msgid == "{{b}}爱{{i}}")
+        .unwrap();
+    assert!(entry.is_fuzzy());
+    assert_eq!(entry.msgstr, ["{{i}} loves {{b}}"]);
+}
+
+#[test]
+fn project_catalog() {
+    const INDEX: &str = "<!I18N>\n<view>一些文字</view>\n<view>首页</view>";
+    const ABOUT: &str = "<!I18N>\n<view>一些文字</view>";
+    assert!(is_i18n_template(INDEX));
+    assert!(!is_i18n_template("<view>一些文字</view>"));
+    let index = search("pages/index/index.wxml", INDEX, &[]).unwrap();
+    let about = search("pages/about/about.wxml", ABOUT, &[]).unwrap();
+    let catalog = terms_to_catalog([&index, &about]);
+    assert_eq!(catalog.entries.len(), 2);
+    assert_eq!(catalog.entries[0].msgid, "一些文字");
+    assert_eq!(
+        catalog.entries[0].references,
+        vec!["pages/index/index.wxml:2", "pages/about/about.wxml:2"]
+    );
+    assert_eq!(catalog.entries[1].msgid, "首页");
+    assert_eq!(
+        catalog.entries[1].references,
+        vec!["pages/index/index.wxml:3"]
+    );
+}
+
+#[test]
+fn plural_terms() {
+    const SRC: &str = r#"<!I18N>
+<view><!I18N plural="count">{{ count }} 个文件</view>"#;
+    let res = search_named("index.wxml", SRC);
+    assert!(res.output[0].plural);
+    let catalog = res.to_catalog();
+    const POT_ENTRY: &str = r#"#: index.wxml:2
+msgid "{{count}} 个文件"
+msgid_plural "{{count}} 个文件"
+msgstr[0] ""
+msgstr[1] ""
+"#;
+    assert!(write_po(&catalog).ends_with(POT_ENTRY));
+
+    // the number of forms follows the language of the existing catalog
+    const EXISTING: &str = r#"msgid ""
+msgstr ""
+"Language: ru\n"
+"Plural-Forms: nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2);\n"
+"#;
+    let existing = parse_po("ru.po", EXISTING).unwrap();
+    assert_eq!(existing.nplurals(), Some(3));
+    let merged = merge_catalog(&catalog, &existing);
+    assert_eq!(merged.entries[0].msgstr.len(), 3);
+}
+
+#[test]
+fn context_terms() {
+    const SRC: &str = r#"<!I18N>
+<view><!I18N context="file">打开</view>
+<view i18n-context="status">打开<text><!I18N context="file">打开</text></view>
+<view>打开</view>"#;
+    let res = search("index.wxml", SRC, &[]).unwrap();
+    let terms: Vec<_> = res
+        .output
+        .iter()
+        .map(|x| (x.context.as_deref(), x.term.as_str(), x.locations.len()))
+        .collect();
+    assert_eq!(
+        terms,
+        [
+            (Some("file"), "打开", 2),
+            (Some("status"), "打开", 1),
+            (None, "打开", 1),
+        ]
+    );
+    const POT_ENTRIES: &str = r#"#: index.wxml:2 index.wxml:3
+msgctxt "file"
+msgid "打开"
+msgstr ""
+
+#: index.wxml:3
+msgctxt "status"
+msgid "打开"
+msgstr ""
+
+#: index.wxml:4
+msgid "打开"
+msgstr ""
+"#;
+    assert!(write_po(&res.to_catalog()).ends_with(POT_ENTRIES));
+}
+
+#[test]
+fn translator_notes() {
+    const SRC: &str = r#"<!I18N>
+<button>
+  <!-- i18n: 文件菜单中的按钮 -->
+  <!I18N context="file" note="打开一个文件">
+  打开
+</button>
+<button><!-- 普通注释 -->关闭</button>
+<view><!-- i18n: 文件菜单中的按钮 -->打开</view>
+<view>
+  <!I18N translate-children note="第一行
+第二行">
+  点击<text>这里</text>
+</view>"#;
+    let res = search_named("index.wxml", SRC);
+    let notes: Vec<_> = res
+        .output
+        .iter()
+        .map(|x| (x.term.as_str(), x.notes.clone()))
+        .collect();
+    assert_eq!(
+        notes,
+        [
+            (
+                "打开",
+                vec!["文件菜单中的按钮".to_string(), "打开一个文件".to_string()]
+            ),
+            ("关闭", vec![]),
+            ("打开", vec!["文件菜单中的按钮".to_string()]),
+            ("这里", vec![]),
+            (
+                "点击{{text}}",
+                vec!["第一行".to_string(), "第二行".to_string()]
+            ),
+        ]
+    );
+    const POT_ENTRY: &str = r#"#. 文件菜单中的按钮
+#. 打开一个文件
+#: index.wxml:4
+msgctxt "file"
+msgid "打开"
+msgstr ""
+"#;
+    assert!(write_po(&res.to_catalog()).contains(POT_ENTRY));
+}
@@ -0,0 +1,103 @@
+use glass_easel_i18n::parse_po;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Create an empty directory for a test in the system temporary directory.
+fn test_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("glass-easel-i18n-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, content: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn search_project(root: &Path) {
+    let status = Command::new(env!("CARGO_BIN_EXE_glass-easel-i18n"))
+        .arg("search-project")
+        .arg(root)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn msgids(path: &Path) -> Vec<String> {
+    let catalog = parse_po(&path.to_string_lossy(), &read(path)).unwrap();
+    catalog.entries.into_iter().map(|x| x.msgid).collect()
+}
+
+#[test]
+fn find_templates() {
+    let root = test_dir("find-templates");
+    const I18N: &str = "<!I18N>\n<view>一些文字</view>";
+    write(&root.join("pages/index/index.wxml"), I18N);
+    write(&root.join("pages/about/about.wxml"), I18N);
+    write(
+        &root.join("pages/plain/plain.wxml"),
+        "<view>一些文字</view>",
+    );
+    write(&root.join("node_modules/lib/lib.wxml"), I18N);
+    write(&root.join(".cache/cache.wxml"), I18N);
+    search_project(&root);
+    assert!(root.join("pages/index/index.locale/messages.pot").exists());
+    assert!(root.join("pages/about/about.locale/messages.pot").exists());
+    assert!(!root.join("pages/plain/plain.locale").exists());
+    assert!(!root.join("node_modules/lib/lib.locale").exists());
+    assert!(!root.join(".cache/cache.locale").exists());
+    let pot = read(&root.join("locale/messages.pot"));
+    assert!(pot.contains("#: pages/about/about.wxml:2 pages/index/index.wxml:2\n"));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn update_catalogs() {
+    let root = test_dir("update-catalogs");
+    write(
+        &root.join("pages/index/index.wxml"),
+        "<!I18N>\n<view>一些文字</view>\n<view>首页</view>",
+    );
+    write(
+        &root.join("pages/about/about.wxml"),
+        "<!I18N>\n<view>一些文字</view>\n<view>关于</view>",
+    );
+    write(
+        &root.join("pages/index/index.locale/en-us.po"),
+        "msgid \"首页\"\nmsgstr \"Home\"\n\nmsgid \"删掉的文字\"\nmsgstr \"Removed words\"\n",
+    );
+    write(
+        &root.join("locale/en-us.po"),
+        "msgid \"一些文字\"\nmsgstr \"Some words\"\n",
+    );
+    search_project(&root);
+
+    // the page catalogs contain all terms of the pages
+    let index_locale = root.join("pages/index/index.locale");
+    assert_eq!(
+        msgids(&index_locale.join("messages.pot")),
+        ["一些文字", "首页"]
+    );
+    assert_eq!(
+        msgids(&root.join("pages/about/about.locale/messages.pot")),
+        ["一些文字", "关于"]
+    );
+    let po = read(&index_locale.join("en-us.po"));
+    assert!(po.contains("msgid \"首页\"\nmsgstr \"Home\"\n"));
+    assert!(po.contains("#~ msgid \"删掉的文字\"\n#~ msgstr \"Removed words\"\n"));
+
+    // the global catalog only contains the terms shared by the pages
+    assert_eq!(msgids(&root.join("locale/messages.pot")), ["一些文字"]);
+    let po = read(&root.join("locale/en-us.po"));
+    assert!(po.contains("msgid \"一些文字\"\nmsgstr \"Some words\"\n"));
+    assert!(!po.contains("首页"));
+    std::fs::remove_dir_all(&root).unwrap();
+}
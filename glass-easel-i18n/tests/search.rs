@@ -1,4 +1,29 @@
-use glass_easel_i18n::search;
+#![allow(clippy::redundant_static_lifetimes)]
+
+use glass_easel_i18n::{
+    is_attribute_included, search, search_with_options, visit_units, DiagnosticKind,
+    ExtractOptions, LiteralPolicy, PlaceholderStyle, SearchOptions, TermOrigin, UnitTarget,
+    UntranslatedTerms, WhitespacePolicy,
+};
+use glass_easel_template_compiler::parse::{parse, tag::Value};
+
+fn terms(res: &UntranslatedTerms) -> String {
+    res.output
+        .iter()
+        .map(|x| x.term.as_str())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Search with the placeholders named after the bindings.
+fn search_named(src: &str, included_attributes: &[String]) -> UntranslatedTerms {
+    let options = SearchOptions {
+        included_attributes: included_attributes.to_vec(),
+        placeholder_style: PlaceholderStyle::Named,
+        ..Default::default()
+    };
+    search_with_options("TEST", src, &options).unwrap()
+}
 
 #[test]
 fn basic() {
@@ -7,7 +32,7 @@ fn basic() {
         <block><view>一些文字</view></block>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "一些文字");
+    assert_eq!(terms(&res), "一些文字");
 }
 
 #[test]
@@ -17,7 +42,7 @@ fn missing() {
         <view>全局的翻译</view>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "全局的翻译");
+    assert_eq!(terms(&res), "全局的翻译");
 }
 
 #[test]
@@ -29,7 +54,7 @@ fn sub_template() {
         </template>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "一些文字");
+    assert_eq!(terms(&res), "一些文字");
 }
 
 #[test]
@@ -39,7 +64,50 @@ fn attributes() {
         <view title="说明" other="其他"></view>
     "#;
     let res = search("TEST", SRC, &["title".into()]).unwrap();
-    assert_eq!(res.output.join("|"), "说明");
+    assert_eq!(terms(&res), "说明");
+}
+
+#[test]
+fn attribute_rules() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view title="说明" placeholder="输入"></view>
+        <my-dialog title="dialog-id" confirm-text="确定"></my-dialog>
+        <button aria-label="关闭" title="按钮"></button>
+    "#;
+    let search_with = |rules: &[&str]| {
+        let options = SearchOptions {
+            included_attributes: rules.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        };
+        search_with_options("TEST", SRC, &options).map(|x| terms(&x))
+    };
+    assert_eq!(search_with(&["title"]).unwrap(), "说明|dialog-id|按钮");
+    assert_eq!(
+        search_with(&["title", "!my-dialog:title", "button[aria-label]"]).unwrap(),
+        "说明|关闭|按钮"
+    );
+    assert_eq!(
+        search_with(&["*:placeholder", "my-dialog:*", "!*[title]"]).unwrap(),
+        "输入|确定"
+    );
+    let err = search_with(&["button[aria-label"]).err().unwrap();
+    assert_eq!(err.kind, DiagnosticKind::InvalidOption);
+}
+
+#[test]
+fn wildcard_attribute_rules() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <my-dialog i18n-context="对话框" confirm-text="确定"></my-dialog>
+    "#;
+    let res = search("TEST", SRC, &["my-dialog:*".into()]).unwrap();
+    assert_eq!(terms(&res), "确定");
+    assert_eq!(res.output[0].context.as_deref(), Some("对话框"));
+    let rules = vec!["*".parse().unwrap()];
+    assert!(is_attribute_included(&rules, "view", "title"));
+    assert!(!is_attribute_included(&rules, "view", "i18n-context"));
+    assert!(!is_attribute_included(&rules, "view", "i18n-ignore"));
 }
 
 #[test]
@@ -49,7 +117,7 @@ fn children() {
         <div><!I18N translate-children>我<span style="color: red">爱</span>你</div>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "爱|我{{A}}你");
+    assert_eq!(terms(&res), "爱|我{{A}}你");
 }
 
 #[test]
@@ -59,7 +127,61 @@ fn placeholders() {
         <view>{{ a }} 加 {{ b }} 得到 {{ a+b }}</view>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "{{A}} 加 {{B}} 得到 {{C}}");
+    assert_eq!(terms(&res), "{{A}} 加 {{B}} 得到 {{C}}");
+}
+
+#[test]
+fn concatenation() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>{{ "共" + (a + b) + "项" }}</view>
+        <view>{{ "价格" + price.toFixed(2) }}</view>
+        <view>{{ a + b + "元" }}</view>
+        <view>{{ "共" + count + "项，" + a + b }}</view>
+        <view>{{ a + b }}</view>
+    "#;
+    let res = search_named(SRC, &[]);
+    assert_eq!(
+        terms(&res),
+        "共{{A}}项|价格{{A}}|{{A}}元|共{{count}}项，{{a}}{{b}}"
+    );
+}
+
+#[test]
+fn named_placeholders() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>{{ user.name }} 有 {{ count }} 条消息，共 {{ count }} 条</view>
+        <view><!I18N placeholders="sum">{{ a + b }} 项</view>
+        <view><!I18N translate-children placeholders=", link">
+            <span>a</span>和<span>b</span>以及<navigator>c</navigator>
+        </view>
+    "#;
+    let res = search_named(SRC, &[]);
+    assert_eq!(
+        terms(&res),
+        "{{user.name}} 有 {{count}} 条消息，共 {{count}} 条|{{sum}} 项|a|b|c|{{span}}和{{link}}以及{{navigator}}"
+    );
+    let options = SearchOptions {
+        placeholder_style: PlaceholderStyle::Letters,
+        ..Default::default()
+    };
+    let res = search_with_options("TEST", SRC, &options).unwrap();
+    assert_eq!(
+        terms(&res),
+        "{{A}} 有 {{B}} 条消息，共 {{C}} 条|{{sum}} 项|a|b|c|{{A}}和{{link}}以及{{C}}"
+    );
+}
+
+#[test]
+fn named_children_and_bindings() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <div><!I18N translate-children>我<span style="color: red">爱</span>你</div>
+        <view>{{ a }} 加 {{ b }} 得到 {{ a+b }}</view>
+    "#;
+    let res = search_named(SRC, &[]);
+    assert_eq!(terms(&res), "爱|我{{span}}你|{{a}} 加 {{b}} 得到 {{C}}");
 }
 
 #[test]
@@ -69,7 +191,7 @@ fn if_block() {
         <view wx:if="{{item.status === 2}}">一些文字</view>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "一些文字");
+    assert_eq!(terms(&res), "一些文字");
 }
 
 #[test]
@@ -79,5 +201,185 @@ fn for_block() {
         <view wx:for="{{ arr }}">一些文字</view>
     "#;
     let res = search("TEST", SRC, &[]).unwrap();
-    assert_eq!(res.output.join("|"), "一些文字");
+    assert_eq!(terms(&res), "一些文字");
+}
+
+#[test]
+fn locations() {
+    const SRC: &'static str = r#"<!I18N>
+<view title="说明">一些文字</view>
+<template name="a">
+    <text>一些文字</text>
+</template>
+<div><!I18N translate-children>我<span>爱</span>你</div>"#;
+    let res = search("TEST", SRC, &["title".into()]).unwrap();
+    assert_eq!(res.path, "TEST");
+    assert_eq!(terms(&res), "说明|一些文字|爱|我{{A}}你");
+
+    let attr = &res.output[0].locations;
+    assert_eq!(attr.len(), 1);
+    assert_eq!(attr[0].origin, TermOrigin::Attribute("title".into()));
+    assert_eq!(attr[0].tag_name.as_deref(), Some("view"));
+    assert_eq!(attr[0].location.start.line, 1);
+
+    let text = &res.output[1].locations;
+    assert_eq!(text.len(), 2);
+    assert_eq!(text[0].origin, TermOrigin::Text);
+    assert_eq!(text[0].location.start.line, 1);
+    assert_eq!(text[0].sub_template, None);
+    assert_eq!(text[1].tag_name.as_deref(), Some("text"));
+    assert_eq!(text[1].location.start.line, 3);
+    assert_eq!(text[1].sub_template.as_deref(), Some("a"));
+
+    let children = &res.output[3].locations;
+    assert_eq!(children[0].origin, TermOrigin::TranslateChildren);
+    assert_eq!(children[0].tag_name.as_deref(), Some("div"));
+    assert_eq!(children[0].location.start.line, 5);
+}
+
+#[test]
+fn ignored() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view title="标题">一些文字</view>
+        <!I18N ignore>
+        <view title="标题"><text>微信</text></view>
+        <view i18n-ignore title="标题"><text>微信</text></view>
+        <view><!I18N ignore>代码</view>
+        <view wx:if="{{ a }}" i18n-ignore>微信</view>
+    "#;
+    let res = search("TEST", SRC, &["title".to_string()]).unwrap();
+    assert_eq!(terms(&res), "标题|一些文字");
+}
+
+#[test]
+fn dynamic_context() {
+    const SRC: &'static str = r#"<!I18N>
+<view i18n-context="{{ ctx }}">打开</view>
+<template name="sub"><view i18n-context="a{{ ctx }}">关闭</view></template>
+"#;
+    let res = search("TEST", SRC, &[]).unwrap();
+    assert_eq!(terms(&res), "打开|关闭");
+    assert!(res.output.iter().all(|x| x.context.is_none()));
+    let warnings: Vec<_> = res.warnings.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        warnings,
+        [
+            "template parse warning at TEST:2:7-2:19: The i18n-context attribute should be a static string, so the data binding is ignored",
+            "template parse warning at TEST:3:28-3:40: The i18n-context attribute should be a static string, so the data binding is ignored",
+        ]
+    );
+}
+
+#[test]
+fn whitespace() {
+    const SRC: &'static str = "<!I18N>
+        <view>
+            一些  文字
+        </view>
+        <view> 共 {{ count }} 条 </view>
+        <view><!I18N translate-children> 我 <span>爱</span>\n  你 </view>";
+    let search_with = |whitespace| {
+        let options = SearchOptions {
+            whitespace,
+            placeholder_style: PlaceholderStyle::Named,
+            ..Default::default()
+        };
+        terms(&search_with_options("TEST", SRC, &options).unwrap())
+    };
+    assert_eq!(
+        search_with(WhitespacePolicy::Trim),
+        "一些  文字|共 {{count}} 条|爱|我{{span}}你"
+    );
+    assert_eq!(
+        search_with(WhitespacePolicy::Collapse),
+        "一些 文字|共 {{count}} 条|爱|我 {{span}} 你"
+    );
+    assert_eq!(
+        search_with(WhitespacePolicy::Preserve),
+        "\n            一些  文字\n        | 共 {{count}} 条 |爱| 我 {{span}}\n  你 "
+    );
+}
+
+#[test]
+fn literals() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view title="{{ a ? '打开' : '关闭' }}">{{ ok ? "成功" : "失败" }}</view>
+        <view>状态：{{ state === "done" ? "完成" : format("进行中", list["key"]) }}</view>
+        <view>{{ name || " 匿名 " }}</view>
+    "#;
+    let search_with = |literals| {
+        let options = SearchOptions {
+            included_attributes: vec!["title".into()],
+            literals,
+            ..Default::default()
+        };
+        terms(&search_with_options("TEST", SRC, &options).unwrap())
+    };
+    assert_eq!(search_with(LiteralPolicy::Ignore), "状态：{{A}}");
+    assert_eq!(
+        search_with(LiteralPolicy::Branches),
+        "打开|关闭|成功|失败|完成|状态：{{A}}|匿名"
+    );
+    assert_eq!(
+        search_with(LiteralPolicy::All),
+        "打开|关闭|成功|失败|完成|进行中|状态：{{A}}|匿名"
+    );
+}
+
+#[test]
+fn children_bindings() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view><!I18N translate-children>你好，{{ user.name }}！你有<text>{{ count }} 条</text>消息{{ a + b }}</view>
+    "#;
+    let res = search_named(SRC, &[]);
+    assert_eq!(
+        terms(&res),
+        "{{count}} 条|你好，{{user.name}}！你有{{text}}消息{{C}}"
+    );
+}
+
+#[test]
+fn visitor() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view i18n-context="menu" title="打开">一些文字</view>
+        <div><!I18N translate-children>我<!-- 注释 --><span>爱</span>你</div>
+    "#;
+    let (mut template, _) = parse("TEST", SRC);
+    let attributes = vec!["title".parse().unwrap()];
+    let options = ExtractOptions::new(&attributes);
+    let mut units = vec![];
+    visit_units(&mut template.content, options, None, &mut |unit| {
+        units.push((unit.message.key(), unit.origin));
+        match unit.target {
+            UnitTarget::Value(value) => {
+                if let Value::Static { value, .. } = value {
+                    *value = "X".into();
+                }
+                true
+            }
+            UnitTarget::Children(_) => false,
+            _ => true,
+        }
+    });
+    assert_eq!(
+        units,
+        [
+            (
+                "menu\u{4}打开".to_string(),
+                TermOrigin::Attribute("title".into())
+            ),
+            ("menu\u{4}一些文字".to_string(), TermOrigin::Text),
+            ("我{{A}}你".to_string(), TermOrigin::TranslateChildren),
+        ]
+    );
+    let mut units = vec![];
+    visit_units(&mut template.content, options, None, &mut |unit| {
+        units.push(unit.message.msgid);
+        true
+    });
+    assert_eq!(units, ["X", "X", "我{{A}}你", "爱"]);
 }
@@ -1,4 +1,10 @@
-use glass_easel_i18n::compile;
+#![allow(clippy::redundant_static_lifetimes)]
+
+use glass_easel_i18n::{
+    compile, compile_per_locale, compile_po, compile_with_options, parse_po, CompileOptions,
+    CompileStrategy, DiagnosticKind, LiteralPolicy, MessageFormat, PlaceholderCheck,
+    PlaceholderStyle, TransContent, WhitespacePolicy,
+};
 
 const TRANSLATE_FILE: &'static str = r#"
 
@@ -22,6 +28,14 @@ const TRANSLATE_FILE: &'static str = r#"
 
 "#;
 
+/// The options with the placeholders named after the bindings.
+fn named_options() -> CompileOptions {
+    CompileOptions {
+        placeholder_style: PlaceholderStyle::Named,
+        ..Default::default()
+    }
+}
+
 #[test]
 fn basic() {
     const SRC: &'static str = r#"
@@ -33,6 +47,25 @@ fn basic() {
     assert_eq!(out.output, OUT);
 }
 
+#[test]
+fn trans_content_map() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>一些文字</view>
+    "#;
+    let messages = [("一些文字".to_string(), "Some words".to_string())];
+    let map = [("en-us".to_string(), messages.into_iter().collect())];
+    let trans_content = TransContent::new(map.clone().into_iter().collect());
+    let out = compile_with_options("TEST", SRC, &trans_content, &Default::default()).unwrap();
+    assert_eq!(out.output, "<block wx:if=\"{{locale===\"en-us\"}}\"><view>Some words</view></block><block wx:else><view>一些文字</view></block>");
+    let trans_content = TransContent {
+        map: map.into_iter().collect(),
+        ..Default::default()
+    };
+    let other = compile_with_options("TEST", SRC, &trans_content, &Default::default()).unwrap();
+    assert_eq!(other.output, out.output);
+}
+
 #[test]
 fn missing() {
     const SRC: &'static str = r#"
@@ -68,6 +101,19 @@ fn attributes() {
     assert_eq!(out.output, OUT);
 }
 
+#[test]
+fn attribute_rules() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view title="说明"></view>
+        <my-dialog title="说明"></my-dialog>
+    "#;
+    const OUT: &'static str = "<block wx:if=\"{{locale===\"en-us\"}}\"><view title=\"explanation\"/><my-dialog title=\"说明\"/></block><block wx:elif=\"{{locale===\"ja\"}}\"><view title=\"説明する\"/><my-dialog title=\"说明\"/></block><block wx:else><view title=\"说明\"/><my-dialog title=\"说明\"/></block>";
+    let rules = ["title".to_string(), "!my-dialog:title".to_string()];
+    let out = compile("TEST", SRC, TRANSLATE_FILE, &rules).unwrap();
+    assert_eq!(out.output, OUT);
+}
+
 #[test]
 fn children() {
     const SRC: &'static str = r#"
@@ -79,6 +125,17 @@ fn children() {
     assert_eq!(out.output, OUT);
 }
 
+#[test]
+fn children_comment() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <div><!I18N translate-children>我<!-- 注释 --><span>爱</span>你</div>
+    "#;
+    const OUT: &'static str = "<block wx:if=\"{{locale===\"en-us\"}}\"><div>I <span>Love</span> You</div></block><block wx:elif=\"{{locale===\"ja\"}}\"><div>愛してます</div></block><block wx:else><div>我<span>爱</span>你</div></block>";
+    let out = compile("TEST", SRC, TRANSLATE_FILE, &[]).unwrap();
+    assert_eq!(out.output, OUT);
+}
+
 #[test]
 fn placeholders() {
     const SRC: &'static str = r#"
@@ -111,3 +168,925 @@ fn for_block() {
     let out = compile("TEST", SRC, TRANSLATE_FILE, &[]).unwrap();
     assert_eq!(out.output, OUT);
 }
+
+#[test]
+fn po_catalogs() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>一些文字</view>
+        <view>全局的翻译</view>
+    "#;
+    const PO: &'static str = r#"
+msgid "一些文字"
+msgstr "Some words"
+
+#, fuzzy
+msgid "全局的翻译"
+msgstr "Fuzzy translation"
+"#;
+    const GLOBAL_PO: &'static str = r#"
+msgid "一些文字"
+msgstr "[Global] Some words"
+
+msgid "全局的翻译"
+msgstr "Global translation"
+"#;
+    const OUT: &'static str = "<block wx:if=\"{{locale===\"en-us\"}}\"><view>Some words</view><view>Global translation</view></block><block wx:else><view>一些文字</view><view>全局的翻译</view></block>";
+    let catalogs = vec![
+        ("en-us".to_string(), parse_po("en-us.po", PO).unwrap()),
+        (
+            "global.en-us".to_string(),
+            parse_po("global/en-us.po", GLOBAL_PO).unwrap(),
+        ),
+    ];
+    let out = compile_po("TEST", SRC, &catalogs, &[]).unwrap();
+    assert_eq!(out.output, OUT);
+}
+
+#[test]
+fn malformed_translation_file() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>一些文字</view>
+    "#;
+    const TRANS: &'static str = r#"
+[en-us]
+"一些文字" = "Some words
+"#;
+    let err = compile("TEST", SRC, TRANS, &[]).err().unwrap();
+    assert_eq!(err.kind, DiagnosticKind::TranslationFileSyntaxError);
+    assert_eq!(err.location.start.line, 2);
+}
+
+#[test]
+fn template_parse_error() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>{{ a + }}</view>
+    "#;
+    let err = compile("TEST", SRC, TRANSLATE_FILE, &[]).err().unwrap();
+    assert_eq!(err.kind, DiagnosticKind::TemplateParseError);
+    assert_eq!(err.path, "TEST");
+    assert_eq!(err.location.start.line, 2);
+}
+
+#[test]
+fn per_locale() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>一些文字</view>
+        <view><!I18N translate-children>我<text>爱</text>你</view>
+        <template name="sub"><view>全局的翻译</view></template>
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANSLATE_FILE).unwrap();
+    let out = compile_per_locale("TEST", SRC, &trans_content, &Default::default()).unwrap();
+    let outputs: Vec<(&str, &str)> = out
+        .locales
+        .iter()
+        .map(|(locale, x)| (locale.as_str(), x.output.as_str()))
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            ("en-us", "<template name=\"sub\"><view>Global translation</view></template><view>Some words</view><view>I <text>Love</text> You</view>"),
+            ("ja", "<template name=\"sub\"><view>全局的翻译</view></template><view>いくつかのテキスト</view><view>愛してます</view>"),
+        ]
+    );
+    assert_eq!(
+        out.fallback.output,
+        "<template name=\"sub\"><view>全局的翻译</view></template><view>一些文字</view><view>我<text>爱</text>你</view>"
+    );
+    assert!(!out.fallback.source_map.is_empty());
+}
+
+#[test]
+fn fine_grained() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view bindtap="onTap" title="说明">一些文字</view>
+        <view>全局的翻译</view>
+        <view>没有翻译</view>
+        <view><!I18N translate-children>我<text>爱</text>你</view>
+    "#;
+    const OUT: &'static str = "<view bindtap=\"onTap\" title=\"{{locale===\"en-us\"?\"explanation\":locale===\"ja\"?\"説明する\":\"说明\"}}\">{{locale===\"en-us\"?\"Some words\":locale===\"ja\"?\"いくつかのテキスト\":\"一些文字\"}}</view><view>{{locale===\"en-us\"?\"Global translation\":\"全局的翻译\"}}</view><view>没有翻译</view><view><block wx:if=\"{{locale===\"en-us\"}}\">I <text>Love</text> You</block><block wx:elif=\"{{locale===\"ja\"}}\">愛してます</block><block wx:else>我<text>爱</text>你</block></view>";
+    let trans_content = TransContent::from_toml("TEST.toml", TRANSLATE_FILE).unwrap();
+    let options = CompileOptions {
+        included_attributes: vec!["title".to_string()],
+        strategy: CompileStrategy::FineGrained,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, OUT);
+}
+
+#[test]
+fn fine_grained_bindings() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>共 {{ count }} 条</view>
+        <view>{{ list.length }}</view>
+    "#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "共 {{count}} 条" = "{{count}} items in total"
+    "#;
+    const OUT: &'static str = "<view>{{locale===\"en-us\"?(count===undefined?\"\":count)+\" items in total\":\"共 \"+(count===undefined?\"\":count)+\" 条\"}}</view><view>{{list.length}}</view>";
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, OUT);
+}
+
+#[test]
+fn lookup_table() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view title="说明">一些文字</view>
+        <view>{{a}} 加 {{b}} 得到 {{c}}</view>
+        <view>没有翻译</view>
+    "#;
+    const OUT: &'static str = "<view title=\"{{i18n.t(locale,\"说明\")}}\">{{i18n.t(locale,\"一些文字\")}}</view><view>{{i18n.t(locale,\"{{A}} 加 {{B}} 得到 {{C}}\",a,b,c)}}</view><view>{{i18n.t(locale,\"没有翻译\")}}</view><wxs module=\"i18n\" src=\"./i18n.wxs\"/>";
+    const JSON: &'static str = r#"{
+  "en-us": {
+    "说明": ["explanation"],
+    "一些文字": ["Some words"],
+    "{{A}} 加 {{B}} 得到 {{C}}": ["Add ", 0, " to ", 1, " to get ", 2]
+  },
+  "ja": {
+    "说明": ["説明する"],
+    "一些文字": ["いくつかのテキスト"],
+    "{{A}} 加 {{B}} 得到 {{C}}": [0, " を ", 1, " に追加すると、", 2, " が得られます"]
+  }
+}
+"#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANSLATE_FILE).unwrap();
+    let options = CompileOptions {
+        included_attributes: vec!["title".to_string()],
+        strategy: CompileStrategy::LookupTable,
+        message_module_src: Some("./i18n.wxs".to_string()),
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, OUT);
+    assert_eq!(out.message_table.unwrap().to_json(), JSON);
+    let options = CompileOptions {
+        message_module_src: Some("./a&b\".wxs".to_string()),
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert!(out
+        .output
+        .ends_with(r#"<wxs module="i18n" src="./a&amp;b&quot;.wxs"/>"#));
+}
+
+#[test]
+fn locale_fallbacks() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>一些文字</view>
+        <view>说明</view>
+    "#;
+    const TRANS: &'static str = r#"
+[zh-tw]
+"一些文字" = "一些文字（繁）"
+
+[zh]
+"一些文字" = "一些文字（简）"
+"说明" = "说明（简）"
+
+[en-gb]
+"说明" = "explanation (GB)"
+
+[en-us]
+"一些文字" = "Some words"
+"说明" = "explanation"
+"#;
+    const OUT: &'static str = "<block wx:if=\"{{locale===\"en-gb\"}}\"><view>Some words</view><view>explanation (GB)</view></block><block wx:elif=\"{{locale===\"en-us\"}}\"><view>Some words</view><view>explanation</view></block><block wx:elif=\"{{locale===\"zh\"}}\"><view>一些文字（简）</view><view>说明（简）</view></block><block wx:elif=\"{{locale===\"zh-hk\"}}\"><view>一些文字（繁）</view><view>说明（简）</view></block><block wx:elif=\"{{locale===\"zh-tw\"}}\"><view>一些文字（繁）</view><view>说明（简）</view></block><block wx:else><view>一些文字</view><view>说明</view></block>";
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let mut options = CompileOptions::default();
+    options.locale_fallbacks.add_chain("zh-hk -> zh-tw -> zh");
+    options.locale_fallbacks.add_chain("en-gb -> en-us");
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, OUT);
+    let fallbacks: Vec<String> = out.fallbacks.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        fallbacks,
+        [
+            "\"一些文字\" in en-gb falls back to en-us",
+            "\"一些文字\" in zh-hk falls back to zh-tw",
+            "\"说明\" in zh-hk falls back to zh",
+            "\"说明\" in zh-tw falls back to zh",
+        ]
+    );
+
+    // the messages rewritten by other strategies are reported in the same way
+    options.strategy = CompileStrategy::LookupTable;
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.fallbacks.len(), 4);
+    let out = compile_per_locale("TEST", SRC, &trans_content, &options).unwrap();
+    let zh_hk = &out.locales.iter().find(|x| x.0 == "zh-hk").unwrap().1;
+    let fallbacks: Vec<String> = zh_hk.fallbacks.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        fallbacks,
+        [
+            "\"一些文字\" in zh-hk falls back to zh-tw",
+            "\"说明\" in zh-hk falls back to zh",
+        ]
+    );
+}
+
+#[test]
+fn locale_expression() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>一些文字</view>
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANSLATE_FILE).unwrap();
+    let mut options = CompileOptions {
+        locale_expression: "app.settings.lang".to_string(),
+        strategy: CompileStrategy::FineGrained,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, "<view>{{app.settings.lang===\"en-us\"?\"Some words\":app.settings.lang===\"ja\"?\"いくつかのテキスト\":\"一些文字\"}}</view>");
+
+    options.locale_expression = "lang || 'ja'".to_string();
+    options.strategy = CompileStrategy::Branches;
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, "<block wx:if=\"{{(lang||\"ja\")===\"en-us\"}}\"><view>Some words</view></block><block wx:elif=\"{{(lang||\"ja\")===\"ja\"}}\"><view>いくつかのテキスト</view></block><block wx:else><view>一些文字</view></block>");
+
+    options.locale_expression = "lang }} {{ other".to_string();
+    let err = compile_with_options("TEST", SRC, &trans_content, &options)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind, DiagnosticKind::InvalidOption);
+}
+
+#[test]
+fn template_locale_forwarding() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <template name="a"><view>一些文字</view></template>
+        <template is="a" />
+        <template is="a" data="{{ a: 1, ...b }}" />
+        <template is="a" data="{{ locale: 'ja' }}" />
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANSLATE_FILE).unwrap();
+    let mut options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, "<template name=\"a\"><view>{{locale===\"en-us\"?\"Some words\":locale===\"ja\"?\"いくつかのテキスト\":\"一些文字\"}}</view></template><template is=\"a\" data=\"{{{locale}}}\"/><template is=\"a\" data=\"{{{locale,a:1,...b}}}\"/><template is=\"a\" data=\"{{{locale:\"ja\"}}}\"/>");
+    assert!(out.warnings.is_empty());
+
+    options.forward_locale = false;
+    options.locale_expression = "settings.lang".to_string();
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.warnings.len(), 3);
+    assert_eq!(out.warnings[0].kind, DiagnosticKind::LocaleNotForwarded);
+    assert_eq!(out.warnings[0].location.start.line, 3);
+}
+
+#[test]
+fn named_placeholders() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <view>{{ user.name }} 有 {{ count }} 条消息，共 {{ count }} 条</view>
+        <view><!I18N placeholders="sum">{{ a + b }} 项</view>
+        <view><!I18N translate-children placeholders="me">
+            <b>我</b>爱<i>你</i>
+        </view>
+    "#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "{{user.name}} 有 {{count}} 条消息，共 {{count}} 条" = "{{count}} messages of {{user.name}}"
+        "{{sum}} 项" = "{{sum}} items"
+        "{{me}}爱{{i}}" = "{{me}} love {{i}}"
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, "<view>{{locale===\"en-us\"?(count===undefined?\"\":count)+\" messages of \"+(user.name===undefined?\"\":user.name):(user.name===undefined?\"\":user.name)+\" 有 \"+(count===undefined?\"\":count)+\" 条消息，共 \"+(count===undefined?\"\":count)+\" 条\"}}</view><view>{{locale===\"en-us\"?(a+b===undefined?\"\":a+b)+\" items\":(a+b===undefined?\"\":a+b)+\" 项\"}}</view><view><block wx:if=\"{{locale===\"en-us\"}}\"><b>我</b> love <i>你</i></block><block wx:else><b>我</b>爱<i>你</i></block></view>");
+}
+
+#[test]
+fn named_placeholders_strategies() {
+    const SRC: &'static str = r#"
+        <!I18N>
+        <div><!I18N translate-children>我<span style="color: red">爱</span>你</div>
+        <view>{{ a }} 加 {{ b }} 得到 {{ a+b }}</view>
+    "#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "我{{span}}你" = "I {{span}} You"
+        "爱" = "Love"
+        "{{a}} 加 {{b}} 得到 {{C}}" = "Add {{a}} to {{b}} to get {{C}}"
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let mut options = named_options();
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, "<block wx:if=\"{{locale===\"en-us\"}}\"><div>I <span style=\"color: red\">Love</span> You</div><view>Add {{a}} to {{b}} to get {{a+b}}</view></block><block wx:else><div>我<span style=\"color: red\">爱</span>你</div><view>{{a}} 加 {{b}} 得到 {{a+b}}</view></block>");
+
+    options.strategy = CompileStrategy::LookupTable;
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, "<div><block wx:if=\"{{locale===\"en-us\"}}\">I <span style=\"color: red\">Love</span> You</block><block wx:else>我<span style=\"color: red\">爱</span>你</block></div><view>{{i18n.t(locale,\"{{a}} 加 {{b}} 得到 {{C}}\",a,b,a+b)}}</view>");
+    const JSON: &'static str = r#"{
+  "en-us": {
+    "{{a}} 加 {{b}} 得到 {{C}}": ["Add ", 0, " to ", 1, " to get ", 2]
+  }
+}
+"#;
+    assert_eq!(out.message_table.unwrap().to_json(), JSON);
+}
+
+#[test]
+fn letter_placeholders() {
+    const TRANS: &'static str = r#"
+        [en-us]
+        "{{A}} 加 {{B}} 得到 {{C}}" = "Add {{A}} to {{B}} to get {{C}}"
+    "#;
+    let binding: String = (0..27).map(|i| format!("{{{{ v{} }}}}", i)).collect();
+    let src = format!(
+        "<!I18N><view>{{{{ a }}}} 加 {{{{ b }}}} 得到 {{{{ a+b }}}}</view><view>{}</view>",
+        binding
+    );
+    let key: String = (0..26)
+        .map(|i| format!("{{{{{}}}}}", (b'A' + i) as char))
+        .collect();
+    let trans = format!("{}\"{}{{{{AA}}}}\" = \"{{{{AA}}}}{{{{A}}}}\"", TRANS, key);
+    let trans_content = TransContent::from_toml("TEST.toml", &trans).unwrap();
+    let options = CompileOptions {
+        placeholder_style: PlaceholderStyle::Letters,
+        ..Default::default()
+    };
+    let out = compile_per_locale("TEST", &src, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.locales[0].1.output,
+        "<view>Add {{a}} to {{b}} to get {{a+b}}</view><view>{{v26}}{{v0}}</view>"
+    );
+}
+
+#[test]
+fn placeholder_mismatch() {
+    const SRC: &'static str = r#"<!I18N>
+<view>{{ count }} 条消息</view>
+<view>共 {{ count }} 条</view>
+<view><!I18N translate-children>我<span>爱</span>你</view>
+<view><!I18N translate-children>共 {{ count }} 条<span>新</span>消息</view>
+"#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "{{count}} 条消息" = "{{count}} messages"
+        "共 {{count}} 条" = "{{total}} in total {{"
+        "我{{span}}你" = "{{span}}{{span}}"
+        "共 {{count}} 条{{span}}消息" = "new messages"
+
+        [ja]
+        "{{count}} 条消息" = "メッセージ"
+        "我{{span}}你" = "愛してます"
+        "共 {{count}} 条{{span}}消息" = "{{count}} 件のメッセージ"
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let mut options = named_options();
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    let warnings: Vec<_> = out.warnings.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        warnings,
+        [
+            "placeholder mismatch at TEST:2:7-2:22: The translation of \"{{count}} 条消息\" in ja has missing placeholder {{count}}: \"メッセージ\"",
+            "placeholder mismatch at TEST:3:9-3:22: The translation of \"共 {{count}} 条\" in en-us has missing placeholder {{count}}, unknown placeholder {{total}}, malformed placeholder: \"{{total}} in total {{\"",
+            "placeholder mismatch at TEST:4:33-4:49: The translation of \"我{{span}}你\" in en-us has duplicated placeholder {{span}}: \"{{span}}{{span}}\"",
+            "placeholder mismatch at TEST:5:35-5:64: The translation of \"共 {{count}} 条{{span}}消息\" in en-us has missing placeholder {{count}}: \"new messages\"",
+        ]
+    );
+
+    // the messages rewritten by each strategy are checked
+    for strategy in [CompileStrategy::FineGrained, CompileStrategy::LookupTable] {
+        options.strategy = strategy;
+        let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+        let other: Vec<_> = out.warnings.iter().map(|x| x.to_string()).collect();
+        assert_eq!(other, warnings);
+    }
+
+    options.placeholder_check = PlaceholderCheck::Error;
+    let err = compile_per_locale("TEST", SRC, &trans_content, &options)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind, DiagnosticKind::PlaceholderMismatch);
+}
+
+#[test]
+fn plural_forms() {
+    const SRC: &'static str = r#"<!I18N>
+<view><!I18N plural="count">{{ count }} 个文件</view>
+"#;
+    const EN_PO: &'static str = r#"
+msgid ""
+msgstr ""
+"Language: en-us\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+msgid "{{count}} 个文件"
+msgid_plural "{{count}} 个文件"
+msgstr[0] "{{count}} file"
+msgstr[1] "{{count}} files"
+"#;
+    const RU_PO: &'static str = r#"
+msgid "{{count}} 个文件"
+msgid_plural "{{count}} 个文件"
+msgstr[0] "{{count}} файл"
+msgstr[1] "{{count}} файла"
+msgstr[2] "{{count}} файлов"
+"#;
+    let catalogs = vec![
+        ("en-us".to_string(), parse_po("en-us.po", EN_PO).unwrap()),
+        ("ru".to_string(), parse_po("ru.po", RU_PO).unwrap()),
+    ];
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view>{{count!=1==0?(count===undefined?"":count)+" file":(count===undefined?"":count)+" files"}}</view></block><block wx:elif="{{locale==="ru"}}"><view>{{(count%10==1&&count%100!=11?0:count%10>=2&&count%10<=4&&(count%100<10||count%100>=20)?1:2)==0?(count===undefined?"":count)+" файл":(count%10==1&&count%100!=11?0:count%10>=2&&count%10<=4&&(count%100<10||count%100>=20)?1:2)==1?(count===undefined?"":count)+" файла":(count===undefined?"":count)+" файлов"}}</view></block><block wx:else><view>{{count}} 个文件</view></block>"#;
+    const LOOKUP_TABLE_OUT: &'static str = r#"<view>{{locale==="en-us"?count!=1==0?(count===undefined?"":count)+" file":(count===undefined?"":count)+" files":locale==="ru"?(count%10==1&&count%100!=11?0:count%10>=2&&count%10<=4&&(count%100<10||count%100>=20)?1:2)==0?(count===undefined?"":count)+" файл":(count%10==1&&count%100!=11?0:count%10>=2&&count%10<=4&&(count%100<10||count%100>=20)?1:2)==1?(count===undefined?"":count)+" файла":(count===undefined?"":count)+" файлов":(count===undefined?"":count)+" 个文件"}}</view>"#;
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let out = compile_with_options("TEST", SRC, &trans_content, &named_options()).unwrap();
+    assert_eq!(out.output, OUT);
+    assert!(out.warnings.is_empty());
+
+    // `t` has no plural rules, so the plural messages are selected in place
+    let options = CompileOptions {
+        strategy: CompileStrategy::LookupTable,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, LOOKUP_TABLE_OUT);
+}
+
+#[test]
+fn default_plural_rules() {
+    const SRC: &'static str = r#"<!I18N>
+<view><!I18N plural="count">{{ count }} 个文件</view>
+"#;
+    const LV_PO: &'static str = r#"
+msgid "{{count}} 个文件"
+msgid_plural "{{count}} 个文件"
+msgstr[0] "{{count}} failu"
+msgstr[1] "{{count}} fails"
+msgstr[2] "{{count}} faili"
+"#;
+    // the catalog has no `Plural-Forms`, so the rule of the locale is used
+    let catalogs = vec![("lv".to_string(), parse_po("lv.po", LV_PO).unwrap())];
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let out = compile_per_locale("TEST", SRC, &trans_content, &named_options()).unwrap();
+    assert_eq!(out.locales[0].1.output, "<view>{{(count%10==0||count%100>=11&&count%100<=19?0:count%10==1&&count%100!=11?1:2)==0?(count===undefined?\"\":count)+\" failu\":(count%10==0||count%100>=11&&count%100<=19?0:count%10==1&&count%100!=11?1:2)==1?(count===undefined?\"\":count)+\" fails\":(count===undefined?\"\":count)+\" faili\"}}</view>");
+}
+
+#[test]
+fn icu_messages() {
+    const SRC: &'static str = r#"<!I18N>
+<view>{{ count }} 个文件</view>
+<view>{{ user.name }} 的文件</view>
+<view>{{ status }} {{ count }} 个文件</view>
+<view>{{ user.name }} 的相册</view>
+"#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "{{count}} 个文件" = "{count, plural, =0 {No files} one {# file} other {# files}}"
+        "{{user.name}} 的文件" = "{user.name}''s files, '{'quoted'}'"
+        "{{status}} {{count}} 个文件" = "{status, select, new {{count, plural, one {# new file} other {# new files}}} other {{{count}} files}}"
+        "{{user.name}} 的相册" = "{user.name, select, other {Albums}"
+    "#;
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view>{{count==0?"No files":count==1?(count===undefined?"":count)+" file":(count===undefined?"":count)+" files"}}</view><view>{{user.name}}'s files, {quoted}</view><view>{{status==="new"?count==1?(count===undefined?"":count)+" new file":(count===undefined?"":count)+" new files":(count===undefined?"":count)+" files"}}</view><view>{{user.name}} 的相册</view></block><block wx:else><view>{{count}} 个文件</view><view>{{user.name}} 的文件</view><view>{{status}} {{count}} 个文件</view><view>{{user.name}} 的相册</view></block>"#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let options = CompileOptions {
+        message_format: MessageFormat::Icu,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, OUT);
+    let warnings: Vec<_> = out.warnings.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        warnings,
+        ["invalid message at TEST:5:7-5:26: The translation of \"{{user.name}} 的相册\" in en-us is not a valid ICU message: expected `}` but found the end"]
+    );
+}
+
+#[test]
+fn icu_children() {
+    const SRC: &'static str = r#"<!I18N>
+<view><!I18N translate-children>共 {{ count }} 个<text>文件</text></view>
+"#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "共 {{count}} 个{{text}}" = "{count, plural, one {# {text}} other {# {text}s}}"
+    "#;
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view>共 {{count}} 个<text>文件</text></view></block><block wx:else><view>共 {{count}} 个<text>文件</text></view></block>"#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let options = CompileOptions {
+        message_format: MessageFormat::Icu,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(out.output, OUT);
+    let warnings: Vec<_> = out.warnings.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        warnings,
+        ["invalid message at TEST:2:35-2:63: The translation of \"共 {{count}} 个{{text}}\" in en-us has plural or select arguments, which are not supported in translate-children groups"]
+    );
+}
+
+#[test]
+fn message_context() {
+    const SRC: &'static str = r#"<!I18N>
+<view><!I18N context="file">打开</view>
+<view i18n-context="status">打开<text title="打开">打开</text></view>
+<view i18n-context="status"><!I18N context="file">打开</view>
+<view>打开</view>
+"#;
+    const PO: &'static str = r#"
+msgid "打开"
+msgstr "Open"
+
+msgctxt "file"
+msgid "打开"
+msgstr "Open file"
+
+msgctxt "status"
+msgid "打开"
+msgstr "On"
+"#;
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view>Open file</view><view>On<text title="On">On</text></view><view>Open file</view><view>Open</view></block><block wx:else><view>打开</view><view>打开<text title="打开">打开</text></view><view>打开</view><view>打开</view></block>"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let out = compile_po("TEST", SRC, &catalogs, &["title".to_string()]).unwrap();
+    assert_eq!(out.output, OUT);
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let options = CompileOptions {
+        included_attributes: vec!["title".to_string()],
+        strategy: CompileStrategy::LookupTable,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        r#"<view>{{i18n.tc(locale,"file","打开")}}</view><view>{{i18n.tc(locale,"status","打开")}}<text title="{{i18n.tc(locale,"status","打开")}}">{{i18n.tc(locale,"status","打开")}}</text></view><view>{{i18n.tc(locale,"file","打开")}}</view><view>{{i18n.t(locale,"打开")}}</view>"#
+    );
+    let table = out.message_table.unwrap().to_json();
+    assert!(table.contains(r#""file\u0004打开": ["Open file"]"#));
+    assert!(table.contains(r#""status\u0004打开": ["On"]"#));
+    assert!(table.contains(r#""打开": ["Open"]"#));
+}
+
+#[test]
+fn dynamic_message_context() {
+    const SRC: &'static str = r#"<!I18N>
+<view i18n-context="{{ ctx }}">打开</view>"#;
+    const PO: &'static str = r#"
+msgid "打开"
+msgstr "Open"
+"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let out = compile_po("TEST", SRC, &catalogs, &[]).unwrap();
+    assert_eq!(
+        out.output,
+        r#"<block wx:if="{{locale==="en-us"}}"><view>Open</view></block><block wx:else><view>打开</view></block>"#
+    );
+    let warnings: Vec<_> = out.warnings.iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        warnings,
+        ["template parse warning at TEST:2:7-2:19: The i18n-context attribute should be a static string, so the data binding is ignored"]
+    );
+}
+
+#[test]
+fn translator_notes() {
+    const SRC: &'static str = r#"<!I18N>
+<view><!-- i18n: 文件菜单中的按钮 --><!I18N context="file" note="打开一个文件">打开</view>"#;
+    const PO: &'static str = r#"
+msgctxt "file"
+msgid "打开"
+msgstr "Open file"
+"#;
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view>Open file</view></block><block wx:else><view>打开</view></block>"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let out = compile_po("TEST", SRC, &catalogs, &[]).unwrap();
+    assert_eq!(out.output, OUT);
+}
+
+#[test]
+fn ignored() {
+    const SRC: &'static str = r#"<!I18N>
+<view title="标题">微信</view>
+<!I18N ignore>
+<view title="标题"><text>微信</text></view>
+<view i18n-ignore title="标题">微信</view>
+<view><!I18N ignore>微信</view>"#;
+    const PO: &'static str = r#"
+msgid "标题"
+msgstr "Title"
+
+msgid "微信"
+msgstr "WeChat"
+"#;
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view title="Title">WeChat</view><view title="标题"><text>微信</text></view><view title="标题">微信</view><view>微信</view></block><block wx:else><view title="标题">微信</view><view title="标题"><text>微信</text></view><view title="标题">微信</view><view>微信</view></block>"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let out = compile_po("TEST", SRC, &catalogs, &["title".to_string()]).unwrap();
+    assert_eq!(out.output, OUT);
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let options = CompileOptions {
+        included_attributes: vec!["title".to_string()],
+        strategy: CompileStrategy::FineGrained,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        r#"<view title="{{locale==="en-us"?"Title":"标题"}}">{{locale==="en-us"?"WeChat":"微信"}}</view><view title="标题"><text>微信</text></view><view title="标题">微信</view><view>微信</view>"#
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::LookupTable,
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        r#"<view title="{{i18n.t(locale,"标题")}}">{{i18n.t(locale,"微信")}}</view><view title="标题"><text>微信</text></view><view title="标题">微信</view><view>微信</view>"#
+    );
+}
+
+#[test]
+fn whitespace() {
+    const SRC: &'static str = "<!I18N>
+<view>
+  一些  文字
+</view>
+<view> 共 {{ count }} 条 </view>
+<view><!I18N translate-children> 我 <text>爱</text>\n  你 </view>";
+    const PO: &'static str = r#"
+msgid "一些 文字"
+msgstr "Some words"
+
+msgid "共 {{count}} 条"
+msgstr "{{count}} in total"
+
+msgid "爱"
+msgstr "love"
+
+msgid "我 {{text}} 你"
+msgstr "I {{text}} you"
+"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let options = CompileOptions {
+        whitespace: WhitespacePolicy::Collapse,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<block wx:if=\"{{locale===\"en-us\"}}\"><view>\n  Some words\n</view><view> {{count}} in total </view><view> I <text>love</text> you </view></block><block wx:else><view>\n  一些  文字\n</view><view> 共 {{count}} 条 </view><view> 我 <text>爱</text>\n  你 </view></block>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view>{{locale===\"en-us\"?\"\\n  Some words\\n\":\"\\n  一些  文字\\n\"}}</view><view>{{locale===\"en-us\"?\" \"+(count===undefined?\"\":count)+\" in total \":\" 共 \"+(count===undefined?\"\":count)+\" 条 \"}}</view><view><block wx:if=\"{{locale===\"en-us\"}}\"> I <text>love</text> you </block><block wx:else> 我 <text>爱</text>\n  你 </block></view>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::LookupTable,
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view>\n  {{i18n.t(locale,\"一些 文字\")}}\n</view><view> {{i18n.t(locale,\"共 {{count}} 条\",count)}} </view><view><block wx:if=\"{{locale===\"en-us\"}}\"> I <text>love</text> you </block><block wx:else> 我 <text>爱</text>\n  你 </block></view>"
+    );
+
+    // the whitespace inside texts is kept by default, and each text of a group is trimmed separately
+    const TRIM_PO: &'static str = r#"
+msgid "一些  文字"
+msgstr "Some words"
+
+msgid "共 {{count}} 条"
+msgstr "{{count}} in total"
+
+msgid "爱"
+msgstr "love"
+
+msgid "我{{text}}你"
+msgstr "I {{text}} you"
+"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", TRIM_PO).unwrap())];
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let out = compile_with_options("TEST", SRC, &trans_content, &named_options()).unwrap();
+    assert_eq!(
+        out.output,
+        "<block wx:if=\"{{locale===\"en-us\"}}\"><view>\n  Some words\n</view><view> {{count}} in total </view><view> I <text>love</text> you </view></block><block wx:else><view>\n  一些  文字\n</view><view> 共 {{count}} 条 </view><view> 我 <text>爱</text>\n  你 </view></block>"
+    );
+
+    // all whitespace is kept in msgids
+    const PRESERVE_PO: &'static str = r#"
+msgid "\n  一些  文字\n"
+msgstr "\nSome words\n"
+
+msgid " 共 {{count}} 条 "
+msgstr " {{count}} in total "
+
+msgid "爱"
+msgstr "love"
+
+msgid " 我 {{text}}\n  你 "
+msgstr " I {{text}} you "
+"#;
+    let catalogs = vec![(
+        "en-us".to_string(),
+        parse_po("en-us.po", PRESERVE_PO).unwrap(),
+    )];
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let options = CompileOptions {
+        whitespace: WhitespacePolicy::Preserve,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<block wx:if=\"{{locale===\"en-us\"}}\"><view>\nSome words\n</view><view> {{count}} in total </view><view> I <text>love</text> you </view></block><block wx:else><view>\n  一些  文字\n</view><view> 共 {{count}} 条 </view><view> 我 <text>爱</text>\n  你 </view></block>"
+    );
+}
+
+#[test]
+fn empty_translation() {
+    const SRC: &'static str = r#"<!I18N>
+<div>hi {{ name }}</div>
+<div>hello</div>"#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "hi {{A}}" = ""
+        "hello" = ""
+    "#;
+    let out = compile("TEST", SRC, TRANS, &[]).unwrap();
+    assert_eq!(
+        out.output,
+        r#"<block wx:if="{{locale==="en-us"}}"><div></div><div></div></block><block wx:else><div>hi {{name}}</div><div>hello</div></block>"#
+    );
+}
+
+#[test]
+fn concatenation() {
+    const SRC: &'static str = r#"<!I18N>
+<view>{{ "共" + (a + b) + "项" }}</view>
+<view>{{ "价格" + price.toFixed(2) }}</view>"#;
+    const PO: &'static str = r#"
+msgid "共{{A}}项"
+msgstr "{{A}} items"
+
+msgid "价格{{A}}"
+msgstr "Price: {{A}}"
+"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let out = compile_with_options("TEST", SRC, &trans_content, &Default::default()).unwrap();
+    assert_eq!(
+        out.output,
+        "<block wx:if=\"{{locale===\"en-us\"}}\"><view>{{a+b}} items</view><view>Price: {{price.toFixed(2)}}</view></block><block wx:else><view>共{{a+b}}项</view><view>价格{{price.toFixed(2)}}</view></block>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view>{{locale===\"en-us\"?(a+b===undefined?\"\":a+b)+\" items\":\"共\"+(a+b)+\"项\"}}</view><view>{{locale===\"en-us\"?\"Price: \"+(price.toFixed(2)===undefined?\"\":price.toFixed(2)):\"价格\"+price.toFixed(2)}}</view>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::LookupTable,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view>{{i18n.t(locale,\"共{{A}}项\",a+b)}}</view><view>{{i18n.t(locale,\"价格{{A}}\",price.toFixed(2))}}</view>"
+    );
+}
+
+#[test]
+fn literals() {
+    const SRC: &'static str = r#"<!I18N>
+<view title="{{ a ? '打开' : '关闭' }}">状态：{{ ok ? "成功" : format("失败", code) }}</view>
+<text>{{ ok ? "成功" : "失败" }}。</text>"#;
+    const PO: &'static str = r#"
+msgid "打开"
+msgstr "Open"
+
+msgid "成功"
+msgstr "succeeded"
+
+msgid "失败"
+msgstr "failed"
+
+msgid "状态：{{A}}"
+msgstr "Status: {{A}}"
+"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let options = CompileOptions {
+        included_attributes: vec!["title".into()],
+        literals: LiteralPolicy::All,
+        ..Default::default()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<block wx:if=\"{{locale===\"en-us\"}}\"><view title=\"{{a?\"Open\":\"关闭\"}}\">Status: {{ok?\"succeeded\":format(\"failed\",code)}}</view><text>{{ok?\"succeeded\":\"failed\"}}。</text></block><block wx:else><view title=\"{{a?\"打开\":\"关闭\"}}\">状态：{{ok?\"成功\":format(\"失败\",code)}}</view><text>{{ok?\"成功\":\"失败\"}}。</text></block>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view title=\"{{a?locale===\"en-us\"?\"Open\":\"打开\":\"关闭\"}}\">{{locale===\"en-us\"?\"Status: \"+((ok?\"succeeded\":format(\"failed\",code))===undefined?\"\":ok?\"succeeded\":format(\"failed\",code)):\"状态：\"+((ok?\"成功\":format(\"失败\",code))===undefined?\"\":ok?\"成功\":format(\"失败\",code))}}</view><text>{{ok?locale===\"en-us\"?\"succeeded\":\"成功\":locale===\"en-us\"?\"failed\":\"失败\"}}。</text>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::LookupTable,
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view title=\"{{a?i18n.t(locale,\"打开\"):i18n.t(locale,\"关闭\")}}\">{{i18n.t(locale,\"状态：{{A}}\",ok?i18n.t(locale,\"成功\"):format(i18n.t(locale,\"失败\"),code))}}</view><text>{{i18n.t(locale,\"{{A}}。\",ok?i18n.t(locale,\"成功\"):i18n.t(locale,\"失败\"))}}</text>"
+    );
+}
+
+#[test]
+fn fine_grained_literals() {
+    const SRC: &'static str = r#"<!I18N>
+<view>状态：{{ ok ? "成功" : "失败" }}</view>
+<view><!I18N translate-children>{{ ok ? "成功" : "失败" }}，<text>详情</text></view>"#;
+    const TRANS: &'static str = r#"
+        [en-us]
+        "状态：{{A}}" = "Status: {{A}}"
+        "{{A}}，{{text}}" = "{{A}}, {{text}}"
+        "成功" = "succeeded"
+        "失败" = "failed"
+        "详情" = "details"
+        [fr]
+        "成功" = "réussi"
+        "失败" = "échoué"
+        "详情" = "détails"
+    "#;
+    let trans_content = TransContent::from_toml("TEST.toml", TRANS).unwrap();
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        literals: LiteralPolicy::Branches,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view>{{locale===\"en-us\"?\"Status: \"+((ok?\"succeeded\":\"failed\")===undefined?\"\":ok?\"succeeded\":\"failed\"):\"状态：\"+((ok?locale===\"fr\"?\"réussi\":\"成功\":locale===\"fr\"?\"échoué\":\"失败\")===undefined?\"\":ok?locale===\"fr\"?\"réussi\":\"成功\":locale===\"fr\"?\"échoué\":\"失败\")}}</view><view><block wx:if=\"{{locale===\"en-us\"}}\">{{ok?\"succeeded\":\"failed\"}}, <text>details</text></block><block wx:else>{{ok?locale===\"fr\"?\"réussi\":\"成功\":locale===\"fr\"?\"échoué\":\"失败\"}}，<text>{{locale===\"fr\"?\"détails\":\"详情\"}}</text></block></view>"
+    );
+    let options = CompileOptions {
+        strategy: CompileStrategy::LookupTable,
+        ..options
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        "<view>{{i18n.t(locale,\"状态：{{A}}\",ok?i18n.t(locale,\"成功\"):i18n.t(locale,\"失败\"))}}</view><view><block wx:if=\"{{locale===\"en-us\"}}\">{{ok?\"succeeded\":\"failed\"}}, <text>details</text></block><block wx:else>{{ok?locale===\"fr\"?\"réussi\":\"成功\":locale===\"fr\"?\"échoué\":\"失败\"}}，<text>{{locale===\"fr\"?\"détails\":\"详情\"}}</text></block></view>"
+    );
+}
+
+#[test]
+fn children_bindings() {
+    const SRC: &'static str = r#"<!I18N>
+<view><!I18N translate-children>你好，{{ user.name }}！你有<text>新</text>消息</view>"#;
+    const PO: &'static str = r#"
+msgid "新"
+msgstr "new"
+
+msgid "你好，{{user.name}}！你有{{text}}消息"
+msgstr "Hello, {{user.name}}! You have {{text}} messages"
+"#;
+    let catalogs = vec![("en-us".to_string(), parse_po("en-us.po", PO).unwrap())];
+    const OUT: &'static str = r#"<block wx:if="{{locale==="en-us"}}"><view>Hello, {{user.name}}! You have <text>new</text> messages</view></block><block wx:else><view>你好，{{user.name}}！你有<text>新</text>消息</view></block>"#;
+    let trans_content = TransContent::from_po_catalogs(&catalogs);
+    let out = compile_with_options("TEST", SRC, &trans_content, &named_options()).unwrap();
+    assert_eq!(out.output, OUT);
+    let options = CompileOptions {
+        strategy: CompileStrategy::FineGrained,
+        ..named_options()
+    };
+    let out = compile_with_options("TEST", SRC, &trans_content, &options).unwrap();
+    assert_eq!(
+        out.output,
+        r#"<view><block wx:if="{{locale==="en-us"}}">Hello, {{user.name}}! You have <text>new</text> messages</block><block wx:else>你好，{{user.name}}！你有<text>新</text>消息</block></view>"#
+    );
+}
@@ -3,7 +3,7 @@ const fs = require('node:fs')
 const path = require('node:path')
 const process = require('node:process')
 const { program } = require('commander')
-const { search } = require('glass-easel-i18n')
+const { searchWithOptions, CompileOptions } = require('glass-easel-i18n')
 
 program
   .command('search')
@@ -11,14 +11,23 @@ program
   .option('-p, --placeholder <placeHolder>', '翻译占位')
   .action((options) => {
     const { file, placeholder } = options
-    // read i18nconfig.json to get included attributes
-    let attributes = []
+    // read i18nconfig.json to get included attributes and how the terms are generated
+    const searchOptions = new CompileOptions()
     const configPath = path.join(process.cwd(), 'i18nconfig.json')
     if (fs.existsSync(configPath)) {
       const i18nConfigContent = fs.readFileSync(configPath, 'utf-8')
       const i18nConfig = JSON.parse(i18nConfigContent)
       if ('attributes' in i18nConfig && Array.isArray(i18nConfig.attributes)) {
-        attributes = [...i18nConfig.attributes]
+        searchOptions.setAttributes([...i18nConfig.attributes])
+      }
+      if (typeof i18nConfig.placeholderStyle === 'string') {
+        searchOptions.setPlaceholderStyle(i18nConfig.placeholderStyle)
+      }
+      if (typeof i18nConfig.whitespace === 'string') {
+        searchOptions.setWhitespace(i18nConfig.whitespace)
+      }
+      if (typeof i18nConfig.literals === 'string') {
+        searchOptions.setLiterals(i18nConfig.literals)
       }
     } else {
       console.log('Config file not found')
@@ -27,15 +36,12 @@ program
     if (fs.existsSync(file)) {
       const source = fs.readFileSync(file, 'utf-8')
       const untranslated = placeholder ?? '尚未翻译'
-      const result = search(file, source, attributes)
+      const result = searchWithOptions(file, source, searchOptions)
       if (result.isSuccess()) {
-        const terms = result
-          .getOutput()
-          .map((term) => `msgid "${term}" \nmsgstr "${untranslated}"`)
-          .join('\n\n')
+        const terms = result.getPo(untranslated)
         fs.writeFileSync(path.join(path.dirname(file), 'untranslated.po'), terms)
       } else {
-        console.log('search untranslated terms error')
+        console.log('search untranslated terms error:', result.getError())
       }
     } else {
       console.log(`Wxml file not found: ${file}`)
@@ -1,81 +1,112 @@
 const path = require('node:path')
 const fs = require('node:fs')
-const { compile } = require('glass-easel-i18n')
+const { compilePoWithOptions, CompileOptions } = require('glass-easel-i18n')
 
-function translateWxml(filename, source, translations, attributes) {
+function translateWxml(filename, source, locales, poSources, poPaths, options) {
   // perform translate calculations by invoking wasm
-  const result = compile(filename, source, translations, attributes)
+  const result = compilePoWithOptions(filename, source, locales, poSources, options, poPaths)
   if (result.isSuccess()) {
+    // eslint-disable-next-line no-console
+    result.getWarnings().forEach((warning) => console.warn(warning))
     return result.getOutput()
   }
+  // eslint-disable-next-line no-console
+  console.error(result.getError())
   return source
 }
 
-async function getPoData(localePath, translations, isGlobal) {
+function getPoData(localePath, locales, poSources, poPaths, isGlobal) {
   if (!fs.existsSync(localePath)) {
     // eslint-disable-next-line no-console
     console.log('Locale files not found: ', localePath)
   } else {
     const localeDir = fs.readdirSync(localePath)
     const prefix = isGlobal ? 'global.' : ''
-    const module = await import('gettext-parser')
-    const gettextParser = module.default
     localeDir.forEach((file) => {
       if (path.extname(file) !== '.po') return
       const poFilePath = path.join(localePath, file)
-      const poData = fs.readFileSync(poFilePath, 'utf8')
       const locale = path.basename(file, '.po')
-      const data = {}
-      const parsedPoData = gettextParser.po.parse(poData)
-      Object.values(parsedPoData.translations['']).forEach((msg) => {
-        if (!msg.msgid || !msg.msgstr.length) {
-          return
-        }
-        data[msg.msgid] = msg.msgstr[0]
-      })
-      translations.push(
-        `
-        ["${prefix}${locale}"]
-        ${Object.entries(data)
-          .map(([key, value]) => `${JSON.stringify(key)} = ${JSON.stringify(value)}`)
-          .join('\n')}
-        `,
-      )
+      locales.push(`${prefix}${locale}`)
+      poSources.push(fs.readFileSync(poFilePath, 'utf8'))
+      poPaths.push(poFilePath)
     })
   }
 }
 
-async function wxmlI18nLoader(source) {
-  const callback = this.async()
-
-  // read i18nconfig.json to get included attributes
-  let attributes = []
-  const configPath = path.join(this.query.configPath, 'i18nconfig.json')
+function readCompileOptions(configRoot) {
+  const options = new CompileOptions()
+  const configPath = path.join(configRoot, 'i18nconfig.json')
   if (fs.existsSync(configPath)) {
     const i18nConfigContent = fs.readFileSync(configPath, 'utf-8')
     const i18nConfig = JSON.parse(i18nConfigContent)
     if ('attributes' in i18nConfig && Array.isArray(i18nConfig.attributes)) {
-      attributes = [...i18nConfig.attributes]
+      options.setAttributes([...i18nConfig.attributes])
+    }
+    if (typeof i18nConfig.strategy === 'string') {
+      options.setStrategy(i18nConfig.strategy)
+    }
+    if (Array.isArray(i18nConfig.fallbacks)) {
+      i18nConfig.fallbacks.forEach((chain) => options.addFallback(chain))
+    }
+    if (typeof i18nConfig.localeExpression === 'string') {
+      options.setLocaleExpression(i18nConfig.localeExpression)
+    }
+    if (typeof i18nConfig.placeholderStyle === 'string') {
+      options.setPlaceholderStyle(i18nConfig.placeholderStyle)
+    }
+    if (typeof i18nConfig.placeholderCheck === 'string') {
+      options.setPlaceholderCheck(i18nConfig.placeholderCheck)
+    }
+    if (typeof i18nConfig.messageFormat === 'string') {
+      options.setMessageFormat(i18nConfig.messageFormat)
     }
+    if (typeof i18nConfig.whitespace === 'string') {
+      options.setWhitespace(i18nConfig.whitespace)
+    }
+    if (typeof i18nConfig.literals === 'string') {
+      options.setLiterals(i18nConfig.literals)
+    }
+    if (typeof i18nConfig.forwardLocale === 'boolean') {
+      options.setForwardLocale(i18nConfig.forwardLocale)
+    }
+  }
+  return options
+}
+
+async function wxmlI18nLoader(source) {
+  const callback = this.async()
+
+  // read i18nconfig.json to get included attributes and other compile options
+  let options
+  try {
+    options = readCompileOptions(this.query.configPath)
+  } catch (err) {
+    // an invalid config value is reported as a loader error instead of hanging the build
+    callback(err)
+    return
   }
 
-  const translations = []
+  const locales = []
+  const poSources = []
+  const poPaths = []
   // global locale files
   const globalLocalePath = path.join(this.query.configPath, 'src/locale')
-  await getPoData(globalLocalePath, translations, true)
+  getPoData(globalLocalePath, locales, poSources, poPaths, true)
 
   // current locale files
   const currentFileName = path.basename(this.resourcePath, '.wxml')
   const currentlocaleDirName = `${currentFileName}.locale`
   const currentlocalePath = path.join(path.dirname(this.resourcePath), currentlocaleDirName)
-  await getPoData(currentlocalePath, translations, false)
+  getPoData(currentlocalePath, locales, poSources, poPaths, false)
 
-  if (translations.length !== 0) {
+  if (locales.length !== 0) {
     const translatedWxml = translateWxml(
       this.resourcePath,
       source,
-      translations.join('\n'),
-      attributes,
+      locales,
+      poSources,
+      poPaths,
+      options,
     )
     callback(null, translatedWxml)
   } else {